@@ -0,0 +1,143 @@
+/*
+    This module defines what a code generator provides to the CLI, and the registry the CLI looks the
+    --target names up in. A new language is added by implementing Backend and registering it in Registry::default,
+    or, by a program using the library, with Registry::register
+*/
+
+use crate::ir::Program;
+use crate::scheme::SchemeBackend;
+use crate::prolog::PrologBackend;
+use crate::python::PythonBackend;
+use crate::r::RBackend;
+
+// Code generator for one target language
+pub trait Backend {
+    // Name given to --target, also the prefix of its own flags (--prolog-text configures the 'text' option of 'prolog')
+    fn name(&self) -> &'static str;
+
+    // Extension of the files the generated code is saved in, without the dot
+    fn file_extension(&self) -> &'static str;
+
+    // Sets an option from the command line: 'dialect' for --dialect, or the option of a --<name>-<option> flag
+    fn configure(&mut self, option: &str, value: &str) -> Result<(), String>;
+
+    // Code of the program; a standalone program includes the runtime, `name` is the name of the DA file
+    // for the languages whose programs are named (e.g. Prolog modules)
+    fn generate(&self, program: &Program, standalone: bool, name: &str) -> String;
+
+    // Warnings about the generated code, e.g. variables renamed to avoid the reserved words of the language
+    fn diagnostics(&self, program: &Program) -> Vec<String>;
+}
+
+// Backends available to --target, in the order they are listed in the usage text
+pub struct Registry {
+    backends: Vec<Box<dyn Backend>>,
+}
+
+impl Registry {
+    pub fn register(&mut self, backend: Box<dyn Backend>) {
+        self.backends.push(backend);
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.backends.iter().map(|backend| backend.name()).collect()
+    }
+
+    // Names and file extensions, e.g. "scheme (.scm)"
+    pub fn describe(&self) -> String {
+        let targets = self.backends.iter()
+            .map(|backend| format!("{} (.{})", backend.name(), backend.file_extension()))
+            .collect::<Vec<String>>();
+        targets.join(", ")
+    }
+
+    // Removes the backend from the registry, so the CLI can configure it
+    pub fn take(&mut self, name: &str) -> Result<Box<dyn Backend>, String> {
+        match self.backends.iter().position(|backend| backend.name() == name) {
+            Some(index) => Ok(self.backends.remove(index)),
+            None => Err(format!("Unknown target '{}', expected one of: {}", name, self.names().join(", "))),
+        }
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Registry { backends: Vec::new() };
+        registry.register(Box::new(SchemeBackend::default()));
+        registry.register(Box::new(PrologBackend::default()));
+        registry.register(Box::new(PythonBackend::default()));
+        registry.register(Box::new(RBackend));
+        registry
+    }
+}
+
+// Text between `quote` characters, escaping the quote, backslashes, newlines, tabs and carriage returns with a backslash
+// as every target does; the other control characters are written by `control`, whose syntax differs between languages,
+// and the other characters as UTF-8
+pub fn quoted(text: &str, quote: char, control: fn(u32) -> String) -> String {
+    let mut result = quote.to_string();
+    for c in text.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            '\r' => result.push_str("\\r"),
+            c if c == quote => {
+                result.push('\\');
+                result.push(c);
+            },
+            c if c.is_control() => result.push_str(&control(c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push(quote);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Echo;
+
+    impl Backend for Echo {
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+
+        fn file_extension(&self) -> &'static str {
+            "txt"
+        }
+
+        fn configure(&mut self, option: &str, _: &str) -> Result<(), String> {
+            Err(format!("no option '{}'", option))
+        }
+
+        fn generate(&self, program: &Program, _: bool, _: &str) -> String {
+            format!("{} instructions", program.instructions.len())
+        }
+
+        fn diagnostics(&self, _: &Program) -> Vec<String> {
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn registered_backends_are_found_by_name() {
+        let mut registry = Registry::default();
+        registry.register(Box::new(Echo));
+        assert_eq!(registry.names(), ["scheme", "prolog", "python", "r", "echo"]);
+        assert!(registry.describe().ends_with("r (.R), echo (.txt)"));
+
+        let echo = registry.take("echo").unwrap();
+        assert_eq!(echo.name(), "echo");
+        assert!(!registry.names().contains(&"echo"));
+        assert_eq!(registry.take("echo").err().unwrap(), "Unknown target 'echo', expected one of: scheme, prolog, python, r");
+    }
+
+    #[test]
+    fn quoted_escapes_with_the_syntax_of_the_target() {
+        assert_eq!(quoted("it's \\ \"ok\"\n", '\'', |code| format!("\\x{:x}\\", code)), "'it\\'s \\\\ \"ok\"\\n'");
+        assert_eq!(quoted("bell\u{7}", '"', |code| format!("\\x{:02x}", code)), "\"bell\\x07\"");
+    }
+}
@@ -0,0 +1,184 @@
+/*
+    This module is the registry of builtin functions: the single place describing each function's name,
+    the types of its arguments, the type it returns and the code emitted for it by every backend
+*/
+
+use crate::types::Type;
+use crate::parser::ExternFunction;
+
+#[derive(Debug)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub parameters: &'static [Type], // one type per argument, so its length is the arity
+    pub result: Type,
+    pub paired: bool, // the vector arguments are paired element by element, so they must have the same length
+    // code templates: {0}, {1}... are replaced by the arguments and {result} by the Prolog variable receiving the value
+    pub scheme: &'static str,
+    pub prolog: &'static str,
+    pub python: &'static str, // standard library
+    pub numpy: &'static str, // pandas and numpy
+    pub r: &'static str,
+}
+
+// Every builtin function. Adding a function is adding an entry here (and its definition to the runtime of each backend)
+pub const BUILTINS: [Builtin; 7] = [
+    Builtin {
+        name: "regressiona",
+        parameters: &[Type::Vector, Type::Vector],
+        result: Type::Number,
+        paired: true,
+        scheme: "(regressiona {0} {1})",
+        prolog: "regressiona({0}, {1}, {result})",
+        python: "statistics.linear_regression({0}, {1}).intercept",
+        numpy: "np.polyfit({0}, {1}, 1)[1]",
+        r: "regression({0}, {1})[[1]]",
+    },
+    Builtin {
+        name: "regressionb",
+        parameters: &[Type::Vector, Type::Vector],
+        result: Type::Number,
+        paired: true,
+        scheme: "(regressionb {0} {1})",
+        prolog: "regressionb({0}, {1}, {result})",
+        python: "statistics.linear_regression({0}, {1}).slope",
+        numpy: "np.polyfit({0}, {1}, 1)[0]",
+        r: "regression({0}, {1})[[2]]",
+    },
+    Builtin {
+        name: "correlation",
+        parameters: &[Type::Vector, Type::Vector],
+        result: Type::Number,
+        paired: true,
+        scheme: "(correlation {0} {1})",
+        prolog: "correlation({0}, {1}, {result})",
+        python: "statistics.correlation({0}, {1})",
+        numpy: "np.corrcoef({0}, {1})[0, 1]",
+        r: "cor({0}, {1})",
+    },
+    Builtin {
+        name: "mean",
+        parameters: &[Type::Vector],
+        result: Type::Number,
+        paired: false,
+        scheme: "(mean {0})",
+        prolog: "mean({0}, {result})",
+        python: "statistics.fmean({0})",
+        numpy: "np.mean({0})",
+        r: "mean({0})",
+    },
+    Builtin {
+        name: "stddev",
+        parameters: &[Type::Vector],
+        result: Type::Number,
+        paired: false,
+        scheme: "(stddev {0})",
+        prolog: "stddev({0}, {result})",
+        python: "statistics.stdev({0})",
+        numpy: "np.std({0}, ddof=1)",
+        r: "sd({0})",
+    },
+    // both regression coefficients at once, as Scheme multiple values, a Prolog list, a Python pair or an R vector (intercept first)
+    Builtin {
+        name: "regression",
+        parameters: &[Type::Vector, Type::Vector],
+        result: Type::Tuple(2),
+        paired: true,
+        scheme: "(regression {0} {1})",
+        prolog: "regression({0}, {1}, {result})",
+        python: "regression({0}, {1})",
+        numpy: "np.polyfit({0}, {1}, 1)[::-1]",
+        r: "regression({0}, {1})",
+    },
+    // summary statistics, as a Scheme association list, a Prolog list of Field-Value pairs, a Python dict or an R named list
+    Builtin {
+        name: "describe",
+        parameters: &[Type::Vector],
+        result: Type::Record,
+        paired: false,
+        scheme: "(describe {0})",
+        prolog: "describe({0}, {result})",
+        python: "describe({0})",
+        numpy: "describe({0})",
+        r: "describe({0})",
+    },
+];
+
+// Finds a builtin by name
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+// A function that can be called from DA: a builtin of the registry or a function of the extern section
+#[derive(Debug, Clone, Copy)]
+pub enum Function<'a> {
+    Builtin(&'static Builtin),
+    Extern(&'a ExternFunction),
+}
+
+impl Function<'_> {
+    pub fn name(&self) -> &str {
+        match self {
+            Function::Builtin(builtin) => builtin.name,
+            Function::Extern(function) => &function.name,
+        }
+    }
+
+    pub fn parameters(&self) -> &[Type] {
+        match self {
+            Function::Builtin(builtin) => builtin.parameters,
+            Function::Extern(function) => &function.parameters,
+        }
+    }
+
+    pub fn result(&self) -> Type {
+        match self {
+            Function::Builtin(builtin) => builtin.result,
+            Function::Extern(function) => function.result,
+        }
+    }
+
+    // Scheme call of the function with converted arguments
+    pub fn scheme_call(&self, args: &[String]) -> String {
+        match self {
+            Function::Builtin(builtin) => render(builtin.scheme, args, ""),
+            Function::Extern(function) => format!("({} {})", function.target_name("scheme"), args.join(" ")),
+        }
+    }
+
+    // Python expression calling the function, with the standard library or with numpy
+    pub fn python_call(&self, args: &[String], numpy: bool) -> String {
+        match self {
+            Function::Builtin(builtin) => render(if numpy { builtin.numpy } else { builtin.python }, args, ""),
+            Function::Extern(function) => format!("{}({})", function.target_name("python"), args.join(", ")),
+        }
+    }
+
+    // R call of the function with converted arguments
+    pub fn r_call(&self, args: &[String]) -> String {
+        match self {
+            Function::Builtin(builtin) => render(builtin.r, args, ""),
+            Function::Extern(function) => format!("{}({})", function.target_name("r"), args.join(", ")),
+        }
+    }
+
+    // Prolog goal binding `result` to the value of the function, passed as the last argument of extern predicates
+    pub fn prolog_goal(&self, args: &[String], result: &str) -> String {
+        match self {
+            Function::Builtin(builtin) => render(builtin.prolog, args, result),
+            Function::Extern(function) => {
+                let mut args = args.to_vec();
+                args.push(result.to_string());
+                format!("{}({})", function.target_name("prolog"), args.join(", "))
+            },
+        }
+    }
+}
+
+// Fills a code template with the converted arguments and the variable receiving the result
+fn render(template: &str, args: &[String], result: &str) -> String {
+    let mut code = template.replace("{result}", result);
+    for (position, arg) in args.iter().enumerate() {
+        code = code.replace(&format!("{{{}}}", position), arg);
+    }
+    code
+}
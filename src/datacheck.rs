@@ -0,0 +1,232 @@
+/*
+    This module checks the CSV files read by the program before any code is generated: every file must exist,
+    every column must be in range and hold numbers, and vectors paired by a function or by element-wise arithmetic must have the same length
+*/
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::parser::{TreeNode, Assignment, Expression, LiteralValue, Comparator, Operator};
+use crate::semantic::Diagnostic;
+use crate::builtins;
+
+// Contents of a CSV file: every row split in fields
+struct Table {
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+        let rows = content.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.split(',').map(|field| field.trim().trim_matches('"').to_string()).collect())
+            .collect();
+        Ok(Table { rows })
+    }
+}
+
+// Checks every read() of the input section against the files, relative to the directory of the program,
+// then checks the lengths of the vectors given to the paired functions of the process section
+pub fn check(nodes: &[TreeNode], directory: &Path) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut tables: HashMap<&str, Result<Table, String>> = HashMap::new();
+    let mut lengths: HashMap<&str, usize> = HashMap::new();
+
+    for node in nodes {
+        match node {
+            TreeNode::Input(assignments) => {
+                for assignment in assignments {
+                    let Assignment::Assign(name, span, Expression::FunctionCall(_, args)) = assignment else {
+                        continue;
+                    };
+                    let [Expression::Literal(LiteralValue::Str(file)), Expression::Literal(LiteralValue::Bool(header)), Expression::Literal(column), filter @ ..] = args.as_slice() else {
+                        continue;
+                    };
+                    let table = tables.entry(file).or_insert_with(|| Table::load(&directory.join(file)));
+                    match table.as_ref().map_err(|e| e.clone()).and_then(|table| read_column(table, file, *header, column, filter.first())) {
+                        Ok(length) => { lengths.insert(name, length); },
+                        Err(e) => diagnostics.push(Diagnostic::error(e, Some(*span))),
+                    }
+                }
+            },
+            TreeNode::Process(assignments) => {
+                for assignment in assignments {
+                    let span = assignment.targets().first().map(|(_, span)| *span);
+                    if let Err(e) = check_pairs(assignment.expression(), &lengths) {
+                        diagnostics.push(Diagnostic::error(e, span));
+                    }
+                    if let Assignment::Assign(name, _, expr) = assignment {
+                        if let Some(length) = length_of(expr, &lengths) {
+                            lengths.insert(name, length);
+                        }
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+    diagnostics
+}
+
+// Checks one column of a file and returns the number of values read, keeping only the rows accepted by the filter
+fn read_column(table: &Table, file: &str, header: bool, column: &LiteralValue, filter: Option<&Expression>) -> Result<usize, String> {
+    let (names, rows) = match table.rows.split_first() {
+        Some((first, rest)) if header => (Some(first), rest),
+        _ => (None, table.rows.as_slice()),
+    };
+    let width = names.or(rows.first()).map_or(0, |row| row.len());
+
+    let index = match column {
+        LiteralValue::Num(n) if (*n as usize) < width => *n as usize,
+        LiteralValue::Num(n) => return Err(format!("Column {} is out of range, {} has {} columns", n, file, width)),
+        LiteralValue::Str(name) => names.and_then(|names| names.iter().position(|field| field == name))
+            .ok_or_else(|| format!("{} has no column named '{}'", file, name))?,
+        LiteralValue::Bool(_) => return Err("Invalid column".to_string()),
+    };
+
+    let mut count = 0;
+    for (position, row) in rows.iter().enumerate() {
+        // line number in the file, counting the header
+        let line = position + 1 + usize::from(names.is_some());
+        if let Some(filter) = filter {
+            if !row_matches(filter, row, line, file)? {
+                continue;
+            }
+        }
+        let value = row.get(index).ok_or_else(|| format!("Line {} of {} has no column {}", line, file, index))?;
+        if value.parse::<f64>().is_err() {
+            return Err(format!("Value '{}' at line {} of {} is not a number", value, line, file));
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+// Evaluates a 'where' filter on a row, so only the rows the program will load are checked and counted
+fn row_matches(filter: &Expression, row: &[String], line: usize, file: &str) -> Result<bool, String> {
+    let Expression::Comparison(left, comparator, right) = filter else {
+        unreachable!("'where' filters are checked to be comparisons by the type checker");
+    };
+    let (left, right) = (evaluate(left, row, line, file)?, evaluate(right, row, line, file)?);
+    Ok(match comparator {
+        Comparator::Less => left < right,
+        Comparator::LessEqual => left <= right,
+        Comparator::Greater => left > right,
+        Comparator::GreaterEqual => left >= right,
+        Comparator::Equal => left == right,
+        Comparator::NotEqual => left != right,
+    })
+}
+
+fn evaluate(expr: &Expression, row: &[String], line: usize, file: &str) -> Result<f64, String> {
+    match expr {
+        Expression::Literal(LiteralValue::Num(n)) => Ok(f64::from(*n)),
+        Expression::FunctionCall(_, args) => {
+            let [Expression::Literal(LiteralValue::Num(n))] = args.as_slice() else {
+                unreachable!("col() is checked to take a column number by the type checker");
+            };
+            let value = row.get(*n as usize).ok_or_else(|| format!("Column {} of the 'where' clause is out of range at line {} of {}", n, line, file))?;
+            value.parse::<f64>()
+                .map_err(|_| format!("Value '{}' at line {} of {} used by the 'where' clause is not a number", value, line, file))
+        },
+        Expression::BinaryOp(left, operator, right) => {
+            let (left, right) = (evaluate(left, row, line, file)?, evaluate(right, row, line, file)?);
+            Ok(match operator {
+                Operator::Add => left + right,
+                Operator::Subtract => left - right,
+                Operator::Multiply => left * right,
+                Operator::Divide => left / right,
+            })
+        },
+        _ => unreachable!("'where' filters only contain col(NUM), numbers and arithmetic"),
+    }
+}
+
+// Vectors given to a paired function (e.g. correlation) or combined by arithmetic must have the same number of values
+fn check_pairs(expr: &Expression, lengths: &HashMap<&str, usize>) -> Result<(), String> {
+    match expr {
+        Expression::FunctionCall(name, args) => {
+            if builtins::lookup(name).is_some_and(|builtin| builtin.paired) {
+                let known = args.iter().filter_map(|arg| length_of(arg, lengths)).collect::<Vec<usize>>();
+                if known.windows(2).any(|pair| pair[0] != pair[1]) {
+                    let sizes = known.iter().map(|length| length.to_string()).collect::<Vec<String>>();
+                    return Err(format!("{}() pairs vectors of different lengths ({} values)", name, sizes.join(" and ")));
+                }
+            }
+            args.iter().try_for_each(|arg| check_pairs(arg, lengths))
+        },
+        // element-wise arithmetic needs vectors of the same length: the generated Prolog fails and R recycles the shortest one
+        Expression::BinaryOp(left, operator, right) => {
+            check_pairs(left, lengths)?;
+            check_pairs(right, lengths)?;
+            match (length_of(left, lengths), length_of(right, lengths)) {
+                (Some(left), Some(right)) if left != right => {
+                    Err(format!("'{}' is applied to vectors of different lengths ({} and {} values)", operator.symbol(), left, right))
+                },
+                _ => Ok(()),
+            }
+        },
+        Expression::Index(left, right) => {
+            check_pairs(left, lengths)?;
+            check_pairs(right, lengths)
+        },
+        Expression::Field(value, _) => check_pairs(value, lengths),
+        _ => Ok(()),
+    }
+}
+
+// Number of values of a vector expression, when it can be known from the files
+fn length_of(expr: &Expression, lengths: &HashMap<&str, usize>) -> Option<usize> {
+    match expr {
+        Expression::Identifier(name, _) => lengths.get(name.as_str()).copied(),
+        Expression::VectorLiteral(elements) => Some(elements.len()),
+        // vectors of different lengths are reported by check_pairs
+        Expression::BinaryOp(left, _, right) => length_of(left, lengths).or_else(|| length_of(right, lengths)),
+        Expression::Slice(vector, start, end) => match (start.as_ref(), end.as_ref()) {
+            (Expression::Literal(LiteralValue::Num(start)), Expression::Literal(LiteralValue::Num(end))) => {
+                let available = length_of(vector, lengths)?;
+                Some((*end as usize).min(available).saturating_sub(*start as usize))
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::{lexer, parser};
+
+    // Errors of a program reading a file with 3 rows (one of them rejected by the filter of y)
+    fn data_errors(process: &str) -> Vec<String> {
+        // every call has its own directory, as the tests run in parallel
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        let directory = std::env::temp_dir().join(format!("datacheck-{}-{}", std::process::id(), CALLS.fetch_add(1, Ordering::Relaxed)));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("a.csv"), "1,2\n3,4\n5,6\n").unwrap();
+        let source = format!("data: x : vector, y : vector, z : vector, n : number \
+            input: x = read(\"a.csv\", false, 0), y = read(\"a.csv\", false, 1) where col(0) > 1 process: {} end.", process);
+        let nodes = parser::Parser::new(lexer::lexical_analysis(&source).unwrap()).parse().unwrap();
+        let errors = check(&nodes, &directory).into_iter().map(|diagnostic| diagnostic.message).collect();
+        fs::remove_dir_all(&directory).unwrap();
+        errors
+    }
+
+    #[test]
+    fn arithmetic_on_vectors_of_different_lengths_is_reported() {
+        assert_eq!(data_errors("z = x - y"), ["'-' is applied to vectors of different lengths (3 and 2 values)"]);
+        assert_eq!(data_errors("z = x * 2 + [1, 2, 3]"), Vec::<String>::new());
+        assert_eq!(data_errors("z = x[0:2] / y"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn paired_functions_need_vectors_of_the_same_length() {
+        assert_eq!(data_errors("n = correlation(x, y)"), ["correlation() pairs vectors of different lengths (3 and 2 values)"]);
+        assert_eq!(data_errors("z = y + 1, n = correlation(x[1:3], z)"), Vec::<String>::new());
+    }
+}
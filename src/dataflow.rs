@@ -0,0 +1,168 @@
+/*
+    This module checks the flow of values through the program: every variable must be assigned once,
+    and the process section must not use a variable before the operation that defines it.
+    It can also reorder the process section so every operation comes after the ones it depends on
+*/
+
+use std::collections::HashMap;
+
+use crate::lexer::Span;
+use crate::parser::{TreeNode, Assignment};
+use crate::semantic::Diagnostic;
+
+// Reports variables assigned more than once and process operations using a variable before it is defined
+pub fn check(nodes: &[TreeNode]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    // position of the first assignment of every variable, in input and process order
+    let mut definitions: HashMap<&str, Span> = HashMap::new();
+    for assignment in assignments(nodes, |node| matches!(node, TreeNode::Input(_) | TreeNode::Process(_))) {
+        for (name, span) in assignment.targets() {
+            match definitions.get(name) {
+                Some(first) => diagnostics.push(Diagnostic::error(
+                    format!("Variable '{}' is assigned twice (first assignment at {})", name, first), Some(span))),
+                None => { definitions.insert(name, span); },
+            }
+        }
+    }
+
+    // the input section is complete before the process section starts
+    let mut defined: Vec<&str> = assignments(nodes, |node| matches!(node, TreeNode::Input(_)))
+        .flat_map(|assignment| assignment.targets())
+        .map(|(name, _)| name)
+        .collect();
+    for assignment in assignments(nodes, |node| matches!(node, TreeNode::Process(_))) {
+        for (name, span) in assignment.expression().identifiers() {
+            if defined.contains(&name) {
+                continue;
+            }
+            let message = match definitions.get(name) {
+                Some(definition) => format!("Variable '{}' is used before it is defined (defined at {})", name, definition),
+                None => format!("Variable '{}' is used but never assigned", name),
+            };
+            diagnostics.push(Diagnostic::error(message, Some(span)));
+        }
+        defined.extend(assignment.targets().into_iter().map(|(name, _)| name));
+    }
+
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.map(|span| (span.line, span.column)));
+    diagnostics
+}
+
+// Reorders the process section so every operation comes after the operations defining the variables it uses.
+// Independent operations keep their source order; operations depending on each other are reported
+pub fn reorder(nodes: &mut [TreeNode]) -> Result<(), Diagnostic> {
+    for node in nodes.iter_mut() {
+        if let TreeNode::Process(operations) = node {
+            let order = topological_order(operations)?;
+            let mut slots: Vec<Option<Assignment>> = operations.drain(..).map(Some).collect();
+            operations.extend(order.into_iter().filter_map(|index| slots[index].take()));
+        }
+    }
+    Ok(())
+}
+
+// Kahn's algorithm over the process operations, always picking the first ready operation in source order
+fn topological_order(operations: &[Assignment]) -> Result<Vec<usize>, Diagnostic> {
+    // operations that must run before each operation
+    let dependencies: Vec<Vec<usize>> = operations.iter().enumerate().map(|(index, operation)| {
+        let used = operation.expression().identifiers();
+        (0..operations.len())
+            .filter(|&other| other != index)
+            .filter(|&other| operations[other].targets().iter().any(|(name, _)| used.iter().any(|(id, _)| id == name)))
+            .collect()
+    }).collect();
+
+    let mut order = Vec::new();
+    let mut done = vec![false; operations.len()];
+    while order.len() < operations.len() {
+        let next = (0..operations.len())
+            .find(|&index| !done[index] && dependencies[index].iter().all(|&dependency| done[dependency]));
+        match next {
+            Some(index) => {
+                done[index] = true;
+                order.push(index);
+            },
+            None => {
+                // every remaining operation waits for another one
+                let remaining = (0..operations.len()).filter(|&index| !done[index]).collect::<Vec<usize>>();
+                let names = remaining.iter()
+                    .flat_map(|&index| operations[index].targets())
+                    .map(|(name, _)| name)
+                    .collect::<Vec<&str>>();
+                let span = operations[remaining[0]].targets().first().map(|(_, span)| *span);
+                return Err(Diagnostic::error(
+                    format!("Process operations defining {} depend on each other", names.join(", ")), span));
+            }
+        }
+    }
+    Ok(order)
+}
+
+// Assignments of the sections selected by the predicate, in program order
+fn assignments(nodes: &[TreeNode], section: fn(&TreeNode) -> bool) -> impl Iterator<Item = &Assignment> {
+    nodes.iter()
+        .filter(move |node| section(node))
+        .flat_map(|node| match node {
+            TreeNode::Input(assignments) | TreeNode::Process(assignments) => assignments.as_slice(),
+            _ => &[],
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer, parser};
+
+    fn parse(process: &str) -> Vec<TreeNode> {
+        let source = format!("data: x : vector, a : number, b : number, c : number input: x = read(\"a.csv\", true, 1) process: {} end.", process);
+        parser::Parser::new(lexer::lexical_analysis(&source).unwrap()).parse().unwrap()
+    }
+
+    // Variables assigned by the process section, in order
+    fn process_order(nodes: &[TreeNode]) -> Vec<&str> {
+        assignments(nodes, |node| matches!(node, TreeNode::Process(_)))
+            .flat_map(|assignment| assignment.targets())
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    fn messages(nodes: &[TreeNode]) -> Vec<String> {
+        check(nodes).into_iter().map(|diagnostic| diagnostic.message).collect()
+    }
+
+    #[test]
+    fn use_before_definition_is_reported() {
+        let nodes = parse("a = b + 1, b = mean(x)");
+        let found = messages(&nodes);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].starts_with("Variable 'b' is used before it is defined"), "{}", found[0]);
+    }
+
+    #[test]
+    fn reorder_puts_definitions_first_and_keeps_independent_operations_in_order() {
+        let mut nodes = parse("c = a + b, a = mean(x), b = stddev(x)");
+        reorder(&mut nodes).unwrap();
+        assert_eq!(process_order(&nodes), ["a", "b", "c"]);
+        assert!(check(&nodes).is_empty());
+
+        let mut nodes = parse("b = mean(x), c = a * 2, a = b + 1");
+        reorder(&mut nodes).unwrap();
+        assert_eq!(process_order(&nodes), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn reorder_reports_cycles() {
+        let mut nodes = parse("a = b + 1, b = a + 1, c = mean(x)");
+        let error = reorder(&mut nodes).unwrap_err();
+        assert_eq!(error.message, "Process operations defining a, b depend on each other");
+    }
+
+    #[test]
+    fn double_assignment_is_reported() {
+        let nodes = parse("a = mean(x), a = stddev(x)");
+        let found = messages(&nodes);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].starts_with("Variable 'a' is assigned twice"), "{}", found[0]);
+    }
+}
@@ -0,0 +1,249 @@
+/*
+    This module builds the dataflow graph of a program, from the columns of the CSV files read by the input section
+    through the variables and the functions of the process section to the destinations of the output section,
+    and writes it as Graphviz DOT or JSON
+*/
+
+use crate::parser::{TreeNode, Assignment, Expression, LiteralValue, Segment};
+use crate::backend;
+
+// Format the graph is written in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Dot,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Column, // column of a CSV file
+    Variable,
+    Function, // one node per call, so two calls of the same function stay apart
+    Output, // console or file written by the output section
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Column => "column",
+            Kind::Variable => "variable",
+            Kind::Function => "function",
+            Kind::Output => "output",
+        }
+    }
+
+    fn shape(self) -> &'static str {
+        match self {
+            Kind::Column => "cylinder",
+            Kind::Variable => "ellipse",
+            Kind::Function => "box",
+            Kind::Output => "note",
+        }
+    }
+}
+
+struct Node {
+    id: String,
+    kind: Kind,
+    label: String,
+}
+
+// Nodes and the edges between them, an edge going from a value to what is computed from it
+pub struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<(usize, usize)>,
+    calls: usize,
+}
+
+// Builds the graph of a program that passed the semantic checks
+pub fn build(nodes: &[TreeNode]) -> Graph {
+    let mut graph = Graph { nodes: Vec::new(), edges: Vec::new(), calls: 0 };
+    for node in nodes {
+        match node {
+            TreeNode::Input(assignments) => {
+                for assignment in assignments {
+                    if let Assignment::Assign(name, _, expr) = assignment {
+                        let variable = graph.variable(name);
+                        graph.add_read(expr, variable);
+                    }
+                }
+            },
+            TreeNode::Process(assignments) => {
+                for assignment in assignments {
+                    let sources = graph.sources(assignment.expression());
+                    for (name, _) in assignment.targets() {
+                        let variable = graph.variable(name);
+                        graph.connect(&sources, variable);
+                    }
+                }
+            },
+            TreeNode::Output(file, expressions) => {
+                let section = graph.output(file.as_deref().unwrap_or("console"));
+                for expr in expressions {
+                    // write(FILE, ...) sends its values to a file of its own
+                    match expr {
+                        Expression::FunctionCall(name, args) if name == "write" => {
+                            if let Some((Expression::Literal(LiteralValue::Str(file)), values)) = args.split_first() {
+                                let destination = graph.output(file);
+                                let sources = values.iter().flat_map(|value| graph.sources(value)).collect::<Vec<usize>>();
+                                graph.connect(&sources, destination);
+                            }
+                        },
+                        _ => {
+                            let sources = graph.sources(expr);
+                            graph.connect(&sources, section);
+                        },
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+    graph
+}
+
+impl Graph {
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Dot => self.dot(),
+            Format::Json => self.json(),
+        }
+    }
+
+    fn dot(&self) -> String {
+        let mut lines = vec!["digraph dataflow {".to_string(), "    rankdir=LR;".to_string()];
+        for node in &self.nodes {
+            lines.push(format!("    {} [label={}, shape={}];", quoted(&node.id), quoted(&node.label), node.kind.shape()));
+        }
+        for (from, to) in &self.edges {
+            lines.push(format!("    {} -> {};", quoted(&self.nodes[*from].id), quoted(&self.nodes[*to].id)));
+        }
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+
+    fn json(&self) -> String {
+        let nodes = self.nodes.iter()
+            .map(|node| format!("    {{\"id\": {}, \"kind\": \"{}\", \"label\": {}}}", quoted(&node.id), node.kind.name(), quoted(&node.label)))
+            .collect::<Vec<String>>();
+        let edges = self.edges.iter()
+            .map(|(from, to)| format!("    {{\"from\": {}, \"to\": {}}}", quoted(&self.nodes[*from].id), quoted(&self.nodes[*to].id)))
+            .collect::<Vec<String>>();
+        format!("{{\n  \"nodes\": [\n{}\n  ],\n  \"edges\": [\n{}\n  ]\n}}", nodes.join(",\n"), edges.join(",\n"))
+    }
+
+    // Index of the node with this id, created the first time it is needed
+    fn node(&mut self, kind: Kind, id: String, label: String) -> usize {
+        match self.nodes.iter().position(|node| node.id == id) {
+            Some(index) => index,
+            None => {
+                self.nodes.push(Node { id, kind, label });
+                self.nodes.len() - 1
+            },
+        }
+    }
+
+    fn variable(&mut self, name: &str) -> usize {
+        self.node(Kind::Variable, format!("variable:{}", name), name.to_string())
+    }
+
+    fn output(&mut self, destination: &str) -> usize {
+        self.node(Kind::Output, format!("output:{}", destination), destination.to_string())
+    }
+
+    fn column(&mut self, file: &str, column: &LiteralValue) -> usize {
+        let column = match column {
+            LiteralValue::Str(name) => format!("\"{}\"", name),
+            LiteralValue::Num(n) => n.to_string(),
+            LiteralValue::Bool(b) => b.to_string(),
+        };
+        self.node(Kind::Column, format!("column:{}:{}", file, column), format!("{}[{}]", file, column))
+    }
+
+    fn connect(&mut self, sources: &[usize], to: usize) {
+        for &from in sources {
+            if !self.edges.contains(&(from, to)) {
+                self.edges.push((from, to));
+            }
+        }
+    }
+
+    // Links the column read into a variable, and the columns its 'where' filter looks at
+    fn add_read(&mut self, expr: &Expression, variable: usize) {
+        let Expression::FunctionCall(_, args) = expr else {
+            return;
+        };
+        let [Expression::Literal(LiteralValue::Str(file)), _, Expression::Literal(column), filter @ ..] = args.as_slice() else {
+            return;
+        };
+        let mut sources = vec![self.column(file, column)];
+        if let Some(filter) = filter.first() {
+            sources.extend(self.filter_sources(file, filter));
+        }
+        self.connect(&sources, variable);
+    }
+
+    fn filter_sources(&mut self, file: &str, filter: &Expression) -> Vec<usize> {
+        match filter {
+            Expression::FunctionCall(_, args) => match args.as_slice() {
+                [Expression::Literal(column)] => vec![self.column(file, column)],
+                _ => unreachable!("col() is checked to take a column number by the type checker"),
+            },
+            Expression::Comparison(left, _, right) | Expression::BinaryOp(left, _, right) => {
+                let mut sources = self.filter_sources(file, left);
+                sources.extend(self.filter_sources(file, right));
+                sources
+            },
+            Expression::Literal(_) => Vec::new(),
+            _ => unreachable!("'where' filters only contain col(NUM), numbers and arithmetic"),
+        }
+    }
+
+    // Nodes an expression takes its value from: the variables it uses, or the calls computing it
+    fn sources(&mut self, expr: &Expression) -> Vec<usize> {
+        match expr {
+            Expression::Literal(_) => Vec::new(),
+            Expression::Identifier(name, _) => vec![self.variable(name)],
+            Expression::FunctionCall(name, args) => {
+                let sources = args.iter().flat_map(|arg| self.sources(arg)).collect::<Vec<usize>>();
+                self.calls += 1;
+                let call = self.node(Kind::Function, format!("call:{}", self.calls), name.clone());
+                self.connect(&sources, call);
+                vec![call]
+            },
+            Expression::VectorLiteral(elements) => elements.iter().flat_map(|element| self.sources(element)).collect(),
+            Expression::Comparison(left, _, right) | Expression::BinaryOp(left, _, right) | Expression::Index(left, right) => {
+                let mut sources = self.sources(left);
+                sources.extend(self.sources(right));
+                sources
+            },
+            Expression::Slice(vector, start, end) => {
+                let mut sources = self.sources(vector);
+                sources.extend(self.sources(start));
+                sources.extend(self.sources(end));
+                sources
+            },
+            Expression::Interpolation(segments) => segments.iter().flat_map(|segment| match segment {
+                Segment::Value(value, _) => self.sources(value),
+                Segment::Text(_) => Vec::new(),
+            }).collect(),
+            Expression::Formatted(value, _) | Expression::Field(value, _) => self.sources(value),
+        }
+    }
+}
+
+// DOT and JSON string, with the control characters written as \uXXXX
+fn quoted(text: &str) -> String {
+    backend::quoted(text, '"', |code| format!("\\u{:04x}", code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_escapes_quotes_backslashes_and_control_characters() {
+        assert_eq!(quoted("my \"file\".csv"), "\"my \\\"file\\\".csv\"");
+        assert_eq!(quoted("a\\b\nc\td\re\u{1}f\u{1f}"), "\"a\\\\b\\nc\\td\\re\\u0001f\\u001f\"");
+    }
+}
@@ -0,0 +1,415 @@
+/*
+    This module holds the intermediate representation (IR) the backends generate code from, and the lowering of the AST into it.
+    Every instruction computes a single operation into a variable of the program or a temporary, each of them
+    assigned exactly once, and every operand carries its resolved type
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::parser::{TreeNode, Assignment, Expression, LiteralValue, Operator, Segment, FileFormat, file_format};
+use crate::types::{Type, TypeEnv};
+use crate::builtins::Function;
+
+// A value used or defined by an instruction
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Variable(String, Type), // variable of the DA program
+    Temporary(usize, Type), // intermediate result, numbered from 1
+    Constant(f64),
+}
+
+impl Operand {
+    pub fn ty(&self) -> Type {
+        match self {
+            Operand::Variable(_, ty) | Operand::Temporary(_, ty) => *ty,
+            Operand::Constant(_) => Type::Number,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Variable(name, _) => write!(f, "{}", name),
+            Operand::Temporary(n, _) => write!(f, "t{}", n),
+            Operand::Constant(value) => write!(f, "{}", number_to_string(*value)),
+        }
+    }
+}
+
+// Column of a CSV file, by position or by header name
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+    Index(u32),
+    Name(String),
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Column::Index(n) => write!(f, "{}", n),
+            Column::Name(name) => write!(f, "\"{}\"", name),
+        }
+    }
+}
+
+// One operation; the first operand(s) of each instruction receive its result
+#[derive(Debug, Clone)]
+pub enum Instruction<'a> {
+    Read(Operand, String, bool, Column, Option<Expression>), // file, header flag, column and optional row filter over col(N)
+    LoadTable(Operand, String, bool), // whole file and header flag, shared by the column extractions reading it
+    Extract(Operand, Operand, Column, Option<Expression>), // table, column and optional row filter
+    Call(Vec<Operand>, Function<'a>, Vec<Operand>), // several results for functions returning a tuple
+    Binary(Operand, Operator, Operand, Operand), // applied element-wise when an operand is a vector
+    Copy(Operand, Operand),
+    Vector(Operand, Vec<Operand>),
+    Index(Operand, Operand, Operand), // vector and position
+    Slice(Operand, Operand, Operand, Operand), // vector, start and (exclusive) end
+    Field(Operand, Operand, String), // record and field name
+}
+
+impl Instruction<'_> {
+    // Operands receiving the result of the instruction
+    pub fn targets(&self) -> Vec<&Operand> {
+        match self {
+            Instruction::Call(targets, _, _) => targets.iter().collect(),
+            Instruction::Read(target, ..) | Instruction::LoadTable(target, ..) | Instruction::Extract(target, ..)
+            | Instruction::Binary(target, ..) | Instruction::Copy(target, _) | Instruction::Vector(target, _) | Instruction::Index(target, ..) | Instruction::Slice(target, ..)
+            | Instruction::Field(target, ..) => vec![target],
+        }
+    }
+
+    // Operands whose value is used by the instruction
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Instruction::Read(..) | Instruction::LoadTable(..) => Vec::new(),
+            Instruction::Call(_, _, args) | Instruction::Vector(_, args) => args.iter().collect(),
+            Instruction::Binary(_, _, left, right) | Instruction::Index(_, left, right) => vec![left, right],
+            Instruction::Copy(_, value) | Instruction::Field(_, value, _) | Instruction::Extract(_, value, ..) => vec![value],
+            Instruction::Slice(_, vector, start, end) => vec![vector, start, end],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Instruction::Read(..) | Instruction::LoadTable(..) => Vec::new(),
+            Instruction::Call(_, _, args) | Instruction::Vector(_, args) => args.iter_mut().collect(),
+            Instruction::Binary(_, _, left, right) | Instruction::Index(_, left, right) => vec![left, right],
+            Instruction::Copy(_, value) | Instruction::Field(_, value, _) | Instruction::Extract(_, value, ..) => vec![value],
+            Instruction::Slice(_, vector, start, end) => vec![vector, start, end],
+        }
+    }
+
+    // Operands the element-wise code of the targets evaluates once per element: the number combined with every
+    // element of a vector (e.g. inside the lambda of a Scheme map or a Python list comprehension)
+    fn looped_operands(&self) -> Vec<&Operand> {
+        match self {
+            Instruction::Binary(_, _, left, right) => match (left.ty() == Type::Vector, right.ty() == Type::Vector) {
+                (true, false) => vec![right],
+                (false, true) => vec![left],
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        }
+    }
+}
+
+// Readable form of an instruction, e.g. "a, b = regression(x, y)"
+impl fmt::Display for Instruction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let targets = self.targets().iter().map(|target| target.to_string()).collect::<Vec<String>>();
+        write!(f, "{} = ", targets.join(", "))?;
+        match self {
+            Instruction::Read(_, file, header, column, filter) => {
+                write!(f, "read(\"{}\", {}, {}){}", file, header, column, if filter.is_some() { " where ..." } else { "" })
+            },
+            Instruction::LoadTable(_, file, header) => write!(f, "load(\"{}\", {})", file, header),
+            Instruction::Extract(_, table, column, filter) => {
+                write!(f, "column({}, {}){}", table, column, if filter.is_some() { " where ..." } else { "" })
+            },
+            Instruction::Call(_, function, args) => {
+                let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+                write!(f, "{}({})", function.name(), args.join(", "))
+            },
+            Instruction::Binary(_, operator, left, right) => {
+                write!(f, "{} {} {}", left, operator.symbol(), right)
+            },
+            Instruction::Copy(_, value) => write!(f, "{}", value),
+            Instruction::Vector(_, elements) => {
+                let elements = elements.iter().map(|element| element.to_string()).collect::<Vec<String>>();
+                write!(f, "[{}]", elements.join(", "))
+            },
+            Instruction::Index(_, vector, index) => write!(f, "{}[{}]", vector, index),
+            Instruction::Slice(_, vector, start, end) => write!(f, "{}[{}:{}]", vector, start, end),
+            Instruction::Field(_, record, field) => write!(f, "{}.{}", record, field),
+        }
+    }
+}
+
+// Piece of a displayed line
+#[derive(Debug, Clone)]
+pub enum Piece {
+    Text(String),
+    Value(Operand, Option<u32>), // value and optional number of decimals
+}
+
+// One operation of the output section
+#[derive(Debug, Clone)]
+pub enum OutputOp {
+    Line(Vec<Piece>), // pieces displayed on a single line
+    Write(String, Vec<(String, Operand)>), // named values saved to a CSV or JSON file
+}
+
+// Operations of an output section and the text file they are sent to instead of the console
+#[derive(Debug, Clone)]
+pub struct OutputSection {
+    pub file: Option<String>,
+    pub operations: Vec<OutputOp>,
+}
+
+impl OutputSection {
+    // Operands displayed or written by the section
+    pub fn operands(&self) -> Vec<&Operand> {
+        self.operations.iter().flat_map(|operation| match operation {
+            OutputOp::Line(pieces) => pieces.iter().filter_map(|piece| match piece {
+                Piece::Value(value, _) => Some(value),
+                Piece::Text(_) => None,
+            }).collect(),
+            OutputOp::Write(_, values) => values.iter().map(|(_, value)| value).collect::<Vec<&Operand>>(),
+        }).collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Program<'a> {
+    pub instructions: Vec<Instruction<'a>>, // input, process and the values displayed by the output, in order
+    pub outputs: Vec<OutputSection>,
+    pub temporaries: usize, // number of temporaries used so far
+}
+
+impl<'a> Program<'a> {
+    // Returns a fresh temporary of the given type
+    pub fn new_temporary(&mut self, ty: Type) -> Operand {
+        self.temporaries += 1;
+        Operand::Temporary(self.temporaries, ty)
+    }
+
+    // Operands used by the output section
+    pub fn output_operands(&self) -> Vec<&Operand> {
+        self.outputs.iter().flat_map(OutputSection::operands).collect()
+    }
+
+    pub fn output_operands_mut(&mut self) -> Vec<&mut Operand> {
+        self.outputs.iter_mut().flat_map(|section| &mut section.operations).flat_map(|operation| match operation {
+            OutputOp::Line(pieces) => pieces.iter_mut().filter_map(|piece| match piece {
+                Piece::Value(value, _) => Some(value),
+                Piece::Text(_) => None,
+            }).collect(),
+            OutputOp::Write(_, values) => values.iter_mut().map(|(_, value)| value).collect::<Vec<&mut Operand>>(),
+        }).collect()
+    }
+}
+
+// Code of the temporaries a backend writes inside the expression using them rather than assigning them: the ones
+// used once, except those an element-wise loop uses, which would otherwise be computed again for every element.
+// Only the rendering of the code is left to the backends
+pub struct Inliner {
+    inlined: HashSet<usize>, // temporaries written where they are used
+    code: HashMap<usize, String>, // code of the inlined temporaries waiting for their use
+}
+
+impl Inliner {
+    pub fn new(program: &Program) -> Self {
+        let mut uses = HashMap::new();
+        let operands = program.instructions.iter().flat_map(|instruction| instruction.operands()).chain(program.output_operands());
+        for operand in operands {
+            if let Operand::Temporary(n, _) = operand {
+                *uses.entry(*n).or_insert(0) += 1;
+            }
+        }
+        let looped = program.instructions.iter().flat_map(|instruction| instruction.looped_operands()).filter_map(|operand| match operand {
+            Operand::Temporary(n, _) => Some(*n),
+            _ => None,
+        }).collect::<HashSet<usize>>();
+        let inlined = uses.into_iter().filter(|(n, count)| *count == 1 && !looped.contains(n)).map(|(n, _)| n).collect();
+        Inliner { inlined, code: HashMap::new() }
+    }
+
+    // Whether the value assigned to `target` is written where it is used instead
+    pub fn inlines(&self, target: &Operand) -> bool {
+        matches!(target, Operand::Temporary(n, _) if self.inlined.contains(n))
+    }
+
+    // Keeps the code computing an inlined temporary until it is used
+    pub fn keep(&mut self, target: &Operand, code: String) {
+        if let Operand::Temporary(n, _) = target {
+            self.code.insert(*n, code);
+        }
+    }
+
+    // Code of an inlined temporary at its use, None for the operands referred to by their name
+    pub fn take(&mut self, operand: &Operand) -> Option<String> {
+        match operand {
+            Operand::Temporary(n, _) => self.code.remove(n),
+            _ => None,
+        }
+    }
+}
+
+// Lowers a type checked program into the IR
+pub fn lower<'a>(nodes: &[TreeNode], types: &'a TypeEnv) -> Program<'a> {
+    let mut lowering = Lowering { types, program: Program { instructions: Vec::new(), outputs: Vec::new(), temporaries: 0 } };
+    for node in nodes {
+        match node {
+            TreeNode::Input(assignments) | TreeNode::Process(assignments) => {
+                for assignment in assignments {
+                    lowering.lower_assignment(assignment);
+                }
+            },
+            TreeNode::Output(file, exprs) => lowering.lower_output(file.as_deref(), exprs),
+            TreeNode::Extern(_) | TreeNode::Data(_) | TreeNode::End => {},
+        }
+    }
+    lowering.program
+}
+
+struct Lowering<'a> {
+    types: &'a TypeEnv,
+    program: Program<'a>,
+}
+
+impl<'a> Lowering<'a> {
+    fn lower_assignment(&mut self, assignment: &Assignment) {
+        match assignment {
+            Assignment::Assign(name, _, expr) => {
+                let target = self.variable(name);
+                self.lower_into(expr, target);
+            },
+            // functions returning a tuple bind every variable at once
+            Assignment::Destructure(names, expr) => {
+                let targets = names.iter().map(|(name, _)| self.variable(name)).collect();
+                if let Expression::FunctionCall(name, args) = expr {
+                    self.lower_call(targets, name, args);
+                }
+            },
+        }
+    }
+
+    fn lower_output(&mut self, file: Option<&str>, exprs: &[Expression]) {
+        // sending the section to a CSV or JSON file saves the listed variables
+        if let Some(file) = file.filter(|file| file_format(file) != FileFormat::Text) {
+            let values = self.named_values(exprs);
+            self.program.outputs.push(OutputSection { file: None, operations: vec![OutputOp::Write(file.to_string(), values)] });
+            return;
+        }
+
+        let mut operations = Vec::new();
+        for expr in exprs {
+            let operation = match expr {
+                Expression::Literal(LiteralValue::Str(text)) => OutputOp::Line(vec![Piece::Text(text.clone())]),
+                Expression::Interpolation(segments) => OutputOp::Line(segments.iter().map(|segment| match segment {
+                    Segment::Text(text) => Piece::Text(text.clone()),
+                    Segment::Value(value, precision) => Piece::Value(self.lower_expression(value), *precision),
+                }).collect()),
+                Expression::Formatted(value, precision) => OutputOp::Line(vec![Piece::Value(self.lower_expression(value), Some(*precision))]),
+                // write(FILE, ID, ...) saves the variables to a file
+                Expression::FunctionCall(name, args) if name == "write" => match args.split_first() {
+                    Some((Expression::Literal(LiteralValue::Str(file)), values)) => OutputOp::Write(file.clone(), self.named_values(values)),
+                    _ => continue,
+                },
+                _ => OutputOp::Line(vec![Piece::Value(self.lower_expression(expr), None)]),
+            };
+            operations.push(operation);
+        }
+        self.program.outputs.push(OutputSection { file: file.map(str::to_string), operations });
+    }
+
+    // Variables saved to a file, with their names
+    fn named_values(&self, exprs: &[Expression]) -> Vec<(String, Operand)> {
+        exprs.iter().filter_map(|expr| match expr {
+            Expression::Identifier(name, _) => Some((name.clone(), self.variable(name))),
+            _ => None,
+        }).collect()
+    }
+
+    // Returns an operand for the value of the expression, adding the instructions computing it
+    fn lower_expression(&mut self, expr: &Expression) -> Operand {
+        match expr {
+            Expression::Literal(LiteralValue::Num(n)) => Operand::Constant(f64::from(*n)),
+            Expression::Identifier(name, _) => self.variable(name),
+            // the number of decimals only matters when the value is displayed
+            Expression::Formatted(value, _) => self.lower_expression(value),
+            _ => {
+                let ty = self.types.type_of(expr).unwrap_or(Type::Number);
+                let temporary = self.program.new_temporary(ty);
+                self.lower_into(expr, temporary.clone());
+                temporary
+            }
+        }
+    }
+
+    // Adds the instructions computing the value of the expression into `target`
+    fn lower_into(&mut self, expr: &Expression, target: Operand) {
+        let instruction = match expr {
+            Expression::FunctionCall(name, args) if name == "read" => match args.as_slice() {
+                [Expression::Literal(LiteralValue::Str(file)), Expression::Literal(LiteralValue::Bool(header)), Expression::Literal(column), filter @ ..] => {
+                    let column = match column {
+                        LiteralValue::Str(name) => Column::Name(name.clone()),
+                        LiteralValue::Num(n) => Column::Index(*n),
+                        LiteralValue::Bool(_) => return,
+                    };
+                    Instruction::Read(target, file.clone(), *header, column, filter.first().cloned())
+                },
+                _ => return,
+            },
+            Expression::FunctionCall(name, args) => return self.lower_call(vec![target], name, args),
+            Expression::BinaryOp(left, operator, right) => {
+                let (left, right) = (self.lower_expression(left), self.lower_expression(right));
+                Instruction::Binary(target, *operator, left, right)
+            },
+            Expression::VectorLiteral(elements) => {
+                let elements = elements.iter().map(|element| self.lower_expression(element)).collect();
+                Instruction::Vector(target, elements)
+            },
+            Expression::Index(vector, index) => {
+                let (vector, index) = (self.lower_expression(vector), self.lower_expression(index));
+                Instruction::Index(target, vector, index)
+            },
+            Expression::Slice(vector, start, end) => {
+                let (vector, start, end) = (self.lower_expression(vector), self.lower_expression(start), self.lower_expression(end));
+                Instruction::Slice(target, vector, start, end)
+            },
+            Expression::Field(record, field) => {
+                let record = self.lower_expression(record);
+                Instruction::Field(target, record, field.clone())
+            },
+            Expression::Formatted(value, _) => return self.lower_into(value, target),
+            // constants and variables are copied
+            Expression::Literal(_) | Expression::Identifier(..) => Instruction::Copy(target, self.lower_expression(expr)),
+            // comparisons only appear in row filters and interpolations in the output section
+            Expression::Comparison(..) | Expression::Interpolation(_) => return,
+        };
+        self.program.instructions.push(instruction);
+    }
+
+    fn lower_call(&mut self, targets: Vec<Operand>, name: &str, args: &[Expression]) {
+        let args = args.iter().map(|arg| self.lower_expression(arg)).collect();
+        if let Some(function) = self.types.function(name) {
+            self.program.instructions.push(Instruction::Call(targets, function, args));
+        }
+    }
+
+    fn variable(&self, name: &str) -> Operand {
+        Operand::Variable(name.to_string(), self.types.variable_type(name).unwrap_or(Type::Number))
+    }
+}
+
+// Writes a constant without decimals when it is a whole number
+pub fn number_to_string(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        value.to_string()
+    }
+}
@@ -4,7 +4,21 @@
     15 October 2023
 */
 
+// position of a token in the source, used to report errors
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
 // lexer enum
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     DATA,
@@ -25,37 +39,98 @@ pub enum Token {
     ASSIGN,
     VECTOR,
     NUMBER,
-    REGRESSIONA,
-    REGRESSIONB,
-    MEAN,
-    STDDEV,
-    CORRELATION,
     STRING(String),
+    WHERE,
+    LT,
+    LE,
+    GT,
+    GE,
+    EQ,
+    NE,
+    LBRACKET,
+    RBRACKET,
+    PLUS,
+    MINUS,
+    TIMES,
+    DIVIDE,
+    TO,
+    RECORD,
+    EXTERN,
 }
 
 // This function performs lexical analysis on the given source string.
 // It scans the string character by character to produce a list of tokens.
 // If something does not match a possible token, it returns a lexical error.
-pub fn lexical_analysis(source: &str) -> Result<Vec<Token>, String> {
+// Every token is paired with the position where it starts.
+pub fn lexical_analysis(source: &str) -> Result<Vec<(Token, Span)>, String> {
     let mut tokens = Vec::new();
-    let mut chars = source.chars().peekable();
+    let mut spans = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    // offsets where each line starts, to turn a character offset into a line and column
+    let line_starts: Vec<usize> = std::iter::once(0).chain(source.match_indices('\n').map(|(i, _)| i + 1)).collect();
+    let span_at = |offset: usize| {
+        let line = line_starts.partition_point(|&start| start <= offset);
+        Span { line, column: offset - line_starts[line - 1] + 1 }
+    };
 
-    while let Some(ch) = chars.next() {
+    while let Some((offset, ch)) = chars.next() {
         match ch {
-            ' ' | '\n' | '\t' => continue, // Skip whitespace and newline
+            ' ' | '\n' | '\t' | '\r' => continue, // Skip whitespace and newline
             ':' => tokens.push(Token::COLON),
             ',' => tokens.push(Token::COMMA),
             '.' => tokens.push(Token::PERIOD),
             '(' => tokens.push(Token::LPAREN),
             ')' => tokens.push(Token::RPAREN),
-            '=' => tokens.push(Token::ASSIGN),
+            '[' => tokens.push(Token::LBRACKET),
+            ']' => tokens.push(Token::RBRACKET),
+            '+' => tokens.push(Token::PLUS),
+            '-' => tokens.push(Token::MINUS),
+            '*' => tokens.push(Token::TIMES),
+            '/' => tokens.push(Token::DIVIDE),
+
+            // '=' is an assignment unless it is followed by another '=' (equality comparison)
+            '=' => {
+                if chars.peek().map(|&(_, c)| c) == Some('=') {
+                    chars.next();
+                    tokens.push(Token::EQ);
+                } else {
+                    tokens.push(Token::ASSIGN);
+                }
+            }
+
+            // comparison operators used by the 'where' clause of input operations
+            '<' => {
+                if chars.peek().map(|&(_, c)| c) == Some('=') {
+                    chars.next();
+                    tokens.push(Token::LE);
+                } else {
+                    tokens.push(Token::LT);
+                }
+            }
+            '>' => {
+                if chars.peek().map(|&(_, c)| c) == Some('=') {
+                    chars.next();
+                    tokens.push(Token::GE);
+                } else {
+                    tokens.push(Token::GT);
+                }
+            }
+            '!' => {
+                if chars.peek().map(|&(_, c)| c) == Some('=') {
+                    chars.next();
+                    tokens.push(Token::NE);
+                } else {
+                    return Err(format!("Unexpected character: ! (expected '!=') at {}", span_at(offset)));
+                }
+            }
 
             // handle strings - denoted by '"'
             '"' => {
                 let mut string_content = String::new();
-                while let Some(&next_ch) = chars.peek() {
+                while let Some(&(_, next_ch)) = chars.peek() {
                     if next_ch != '"' {
-                        string_content.push(chars.next().unwrap());
+                        string_content.push(chars.next().unwrap().1);
                     } else {
                         chars.next();  // Consume the closing quotation mark
                         break;
@@ -67,9 +142,9 @@ pub fn lexical_analysis(source: &str) -> Result<Vec<Token>, String> {
             // handle numbers
             '0'..='9' => {
                 let mut num = ch.to_string();
-                while let Some(next_ch) = chars.peek() {
+                while let Some(&(_, next_ch)) = chars.peek() {
                     if next_ch.is_ascii_digit() {
-                        num.push(chars.next().unwrap());
+                        num.push(chars.next().unwrap().1);
                     } else {
                         break;
                     }
@@ -79,16 +154,16 @@ pub fn lexical_analysis(source: &str) -> Result<Vec<Token>, String> {
                 if let Ok(parsed_num) = pars_result {
                     tokens.push(Token::NUM(parsed_num));
                 } else {
-                    return Err(format!("Failed to parse number: {}", num));
+                    return Err(format!("Failed to parse number: {} at {}", num, span_at(offset)));
                 }
             }
 
             // Recognize identifiers, numbers, strings, etc.
             'a'..='z' => {
                 let mut id = ch.to_string();
-                while let Some(next_ch) = chars.peek() {
+                while let Some(&(_, next_ch)) = chars.peek() {
                     if next_ch.is_ascii_alphabetic() {
-                        id.push(chars.next().unwrap());
+                        id.push(chars.next().unwrap().1);
                     } else {
                         break;
                     }
@@ -106,11 +181,10 @@ pub fn lexical_analysis(source: &str) -> Result<Vec<Token>, String> {
                     "read" => tokens.push(Token::READ),
                     "vector" => tokens.push(Token::VECTOR),
                     "number" => tokens.push(Token::NUMBER),
-                    "regressiona" => tokens.push(Token::REGRESSIONA),
-                    "regressionb" => tokens.push(Token::REGRESSIONB),
-                    "mean" => tokens.push(Token::MEAN),
-                    "stddev" => tokens.push(Token::STDDEV),
-                    "correlation" => tokens.push(Token::CORRELATION),
+                    "record" => tokens.push(Token::RECORD),
+                    "where" => tokens.push(Token::WHERE),
+                    "to" => tokens.push(Token::TO),
+                    "extern" => tokens.push(Token::EXTERN),
 
                     _ => tokens.push(Token::ID(id)),
                 }
@@ -118,9 +192,18 @@ pub fn lexical_analysis(source: &str) -> Result<Vec<Token>, String> {
             }
 
             // If we encounter an unrecognized character, return an error
-            _ => return Err(format!("Unexpected character: {}", ch)),
+            _ => return Err(format!("Unexpected character: {} at {}", ch, span_at(offset))),
         }
+
+        // the token pushed by this iteration starts at the current character
+        spans.resize(tokens.len(), span_at(offset));
     }
 
-    Ok(tokens)
-}
\ No newline at end of file
+    Ok(tokens.into_iter().zip(spans).collect())
+}
+
+// Identifiers start with a lowercase letter followed by any ASCII letters, as recognized by lexical_analysis
+pub fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|first| first.is_ascii_lowercase()) && chars.all(|c| c.is_ascii_alphabetic())
+}
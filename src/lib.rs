@@ -0,0 +1,24 @@
+/*
+    This module is the library behind the CLI: every stage of the compiler, from the lexer to the backends.
+    A program embedding it adds its own language by implementing Backend and registering it in a Registry
+*/
+
+pub mod lexer;
+pub mod parser;
+pub mod scheme;
+pub mod prolog;
+pub mod python;
+pub mod r;
+pub mod types;
+pub mod semantic;
+pub mod dataflow;
+pub mod lint;
+pub mod builtins;
+pub mod datacheck;
+pub mod ir;
+pub mod optimize;
+pub mod graph;
+pub mod names;
+pub mod backend;
+
+pub use backend::{Backend, Registry};
@@ -0,0 +1,239 @@
+/*
+    This module runs the lint rules: checks for programs that are valid but most likely wrong.
+    Every rule has a name and a level (allow, warn or deny) that a project can change in its lint configuration file
+*/
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::lexer::Span;
+use crate::parser::{TreeNode, Declaration, Assignment, Expression, Segment};
+use crate::semantic::Diagnostic;
+
+// Name of the lint configuration file, looked up next to the program
+pub const CONFIG_FILE: &str = "dalint.conf";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Allow, // the rule is not run
+    Warn,  // findings are reported and the compilation continues
+    Deny,  // findings are errors
+}
+
+// A lint rule: its name and the function returning its findings
+struct Rule {
+    name: &'static str,
+    check: fn(&[TreeNode]) -> Vec<(String, Span)>,
+}
+
+// Every rule, in the order they are run. To add a rule write its check function and list it here
+const RULES: [Rule; 5] = [
+    Rule { name: "unused_variable", check: unused_variable },
+    Rule { name: "unread_variable", check: unread_variable },
+    Rule { name: "unassigned_output", check: unassigned_output },
+    Rule { name: "duplicate_read", check: duplicate_read },
+    Rule { name: "self_correlation", check: self_correlation },
+];
+
+// Level of every rule, all rules warn unless configured otherwise
+pub struct LintConfig {
+    levels: HashMap<&'static str, Level>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig { levels: RULES.iter().map(|rule| (rule.name, Level::Warn)).collect() }
+    }
+}
+
+impl LintConfig {
+    // Reads the configuration file if it exists. Each line sets the level of a rule, e.g.
+    //   self_correlation = deny
+    // blank lines and lines starting with '#' are ignored
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let mut config = LintConfig::default();
+        let Ok(content) = fs::read_to_string(path) else {
+            return Ok(config);
+        };
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, level) = line.split_once('=')
+                .ok_or_else(|| format!("Expected 'rule = level' at line {} of {}", number + 1, path.display()))?;
+            let rule = RULES.iter().find(|rule| rule.name == name.trim())
+                .ok_or_else(|| format!("Unknown lint rule '{}' at line {} of {}", name.trim(), number + 1, path.display()))?;
+            let level = match level.trim() {
+                "allow" => Level::Allow,
+                "warn" => Level::Warn,
+                "deny" => Level::Deny,
+                other => return Err(format!("Unknown lint level '{}' at line {} of {} (use allow, warn or deny)", other, number + 1, path.display())),
+            };
+            config.levels.insert(rule.name, level);
+        }
+        Ok(config)
+    }
+
+    // Turns every warning into an error
+    pub fn deny_warnings(&mut self) {
+        for level in self.levels.values_mut() {
+            if *level == Level::Warn {
+                *level = Level::Deny;
+            }
+        }
+    }
+}
+
+// Runs the rules that are not allowed; denied rules report errors and the others warnings
+pub fn run(nodes: &[TreeNode], config: &LintConfig) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for rule in &RULES {
+        let level = config.levels[rule.name];
+        if level == Level::Allow {
+            continue;
+        }
+        for (message, span) in (rule.check)(nodes) {
+            let message = format!("{} [{}]", message, rule.name);
+            diagnostics.push(match level {
+                Level::Deny => Diagnostic::error(message, Some(span)),
+                _ => Diagnostic::warning(message, Some(span)),
+            });
+        }
+    }
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.map(|span| (span.line, span.column)));
+    diagnostics
+}
+
+// Declared variables that are neither assigned nor read
+fn unused_variable(nodes: &[TreeNode]) -> Vec<(String, Span)> {
+    let (assigned, read) = (assigned_variables(nodes), read_variables(nodes));
+    declarations(nodes).into_iter()
+        .filter(|(name, _)| !assigned.contains(name) && !read.contains(name))
+        .map(|(name, span)| (format!("Variable '{}' is declared but never used", name), span))
+        .collect()
+}
+
+// Variables that are assigned but whose value is never used
+fn unread_variable(nodes: &[TreeNode]) -> Vec<(String, Span)> {
+    let (assigned, read) = (assigned_variables(nodes), read_variables(nodes));
+    declarations(nodes).into_iter()
+        .filter(|(name, _)| assigned.contains(name) && !read.contains(name))
+        .map(|(name, span)| (format!("Variable '{}' is assigned but never read", name), span))
+        .collect()
+}
+
+// Output operations displaying a variable that never receives a value
+fn unassigned_output(nodes: &[TreeNode]) -> Vec<(String, Span)> {
+    let assigned = assigned_variables(nodes);
+    output_expressions(nodes)
+        .flat_map(|expr| expr.identifiers())
+        .filter(|(name, _)| !assigned.contains(name))
+        .map(|(name, span)| (format!("Variable '{}' is displayed but never assigned", name), span))
+        .collect()
+}
+
+// The same column of the same file (with the same filter) read into two variables
+fn duplicate_read(nodes: &[TreeNode]) -> Vec<(String, Span)> {
+    let mut findings = Vec::new();
+    let mut reads: Vec<(&str, &[Expression])> = Vec::new();
+    for node in nodes {
+        if let TreeNode::Input(assignments) = node {
+            for assignment in assignments {
+                let Assignment::Assign(name, span, Expression::FunctionCall(function, args)) = assignment else {
+                    continue;
+                };
+                if function != "read" || args.len() < 3 {
+                    continue;
+                }
+                // the header flag does not change which values are read
+                let same_read = |other: &[Expression]| other[0] == args[0] && other[2] == args[2] && other.get(3) == args.get(3);
+                match reads.iter().find(|(_, other)| same_read(other)) {
+                    Some((first, _)) => findings.push((format!("Variable '{}' reads the same column as '{}'", name, first), *span)),
+                    None => reads.push((name, args)),
+                }
+            }
+        }
+    }
+    findings
+}
+
+// correlation(x, x) is always 1
+fn self_correlation(nodes: &[TreeNode]) -> Vec<(String, Span)> {
+    let mut findings = Vec::new();
+    let expressions = nodes.iter().flat_map(|node| match node {
+        TreeNode::Process(assignments) => assignments.iter().map(|assignment| assignment.expression()).collect(),
+        _ => Vec::new(),
+    }).chain(output_expressions(nodes));
+    for expr in expressions {
+        collect_self_correlations(expr, &mut findings);
+    }
+    findings
+}
+
+fn collect_self_correlations(expr: &Expression, findings: &mut Vec<(String, Span)>) {
+    match expr {
+        Expression::FunctionCall(name, args) => {
+            if let [Expression::Identifier(x, span), Expression::Identifier(y, _)] = args.as_slice() {
+                if name == "correlation" && x == y {
+                    findings.push((format!("Correlation of '{}' with itself is always 1", x), *span));
+                }
+            }
+            for arg in args {
+                collect_self_correlations(arg, findings);
+            }
+        },
+        Expression::BinaryOp(left, _, right) | Expression::Index(left, right) => {
+            collect_self_correlations(left, findings);
+            collect_self_correlations(right, findings);
+        },
+        Expression::Formatted(value, _) | Expression::Field(value, _) => collect_self_correlations(value, findings),
+        Expression::Interpolation(segments) => {
+            for segment in segments {
+                if let Segment::Value(value, _) = segment {
+                    collect_self_correlations(value, findings);
+                }
+            }
+        },
+        _ => {}
+    }
+}
+
+// Declared variables with the position of their declaration
+fn declarations(nodes: &[TreeNode]) -> Vec<(&str, Span)> {
+    nodes.iter().flat_map(|node| match node {
+        TreeNode::Data(decls) => decls.iter().map(|declaration| match declaration {
+            Declaration::Vector(name, span) | Declaration::Number(name, span) | Declaration::Record(name, span) => (name.as_str(), *span),
+        }).collect(),
+        _ => Vec::new(),
+    }).collect()
+}
+
+// Variables receiving a value in the input or process sections
+fn assigned_variables(nodes: &[TreeNode]) -> Vec<&str> {
+    nodes.iter().flat_map(|node| match node {
+        TreeNode::Input(assignments) | TreeNode::Process(assignments) => {
+            assignments.iter().flat_map(|assignment| assignment.targets()).map(|(name, _)| name).collect()
+        },
+        _ => Vec::new(),
+    }).collect()
+}
+
+// Variables whose value is used by an expression of the program
+fn read_variables(nodes: &[TreeNode]) -> Vec<&str> {
+    nodes.iter().flat_map(|node| match node {
+        TreeNode::Input(assignments) | TreeNode::Process(assignments) => {
+            assignments.iter().flat_map(|assignment| assignment.expression().identifiers()).map(|(name, _)| name).collect()
+        },
+        TreeNode::Output(_, exprs) => exprs.iter().flat_map(|expr| expr.identifiers()).map(|(name, _)| name).collect(),
+        _ => Vec::new(),
+    }).collect()
+}
+
+fn output_expressions(nodes: &[TreeNode]) -> impl Iterator<Item = &Expression> {
+    nodes.iter().flat_map(|node| match node {
+        TreeNode::Output(_, exprs) => exprs.as_slice(),
+        _ => &[],
+    })
+}
@@ -1,40 +1,126 @@
 /* 
     This module is the main which handles the CLI (argument checks) and links together all other modules
-    (lexer.rs, parser.rs, scheme.rs, and prolog.rs)
+    (lexer.rs, parser.rs, scheme.rs, prolog.rs and the others of the library in lib.rs)
     Chris Kendall
     15 October 2023
 */
 
-mod lexer;
-mod parser;
-mod scheme;
-mod prolog;
-
 use std::env;
 use std::fs;
+use std::path::Path;
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+use rust_parser_assignment_02::{lexer, parser, semantic, dataflow, types, lint, datacheck, ir, optimize, graph};
+use rust_parser_assignment_02::{Backend, Registry};
 
-    if args.len() != 3 {
-        println!("Usage: cargo run <filename> [-s|-p]");
-        return;
+// What is produced from the program
+enum Command {
+    Generate(Box<dyn Backend>), // code in the language of the backend chosen by --target
+    Graph(graph::Format), // dataflow graph instead of code
+}
+
+// Settings chosen on the command line
+struct Options {
+    filename: String,
+    command: Command,
+    standalone: bool, // complete program: the runtime prelude of Scheme and Prolog, the shebang line of the scripts that always include it
+    reorder: bool, // sort the process section by its dependencies instead of rejecting out of order uses
+    deny_warnings: bool, // lint warnings stop the compilation (for CI)
+    check_data: bool, // open the CSV files read by the program to check their columns
+    explain_optimizations: bool, // report what the optimizer removed or folded
+}
+
+// Reads the command line: the input file, --target <name> (or -s and -p for Scheme and Prolog), then optional flags.
+// Flags starting with the name of a target (--prolog-text) are options of that target.
+// 'graph' before the input file writes the dataflow graph instead, as DOT unless --json is given
+fn parse_arguments(args: &[String]) -> Result<Options, String> {
+    let mut registry = Registry::default();
+    let usage = format!("Usage: cargo run <filename> [--target <name>|-s|-p] [--standalone [--dialect <name>]] [--<target>-<option> <value>] [--reorder] [--deny-warnings] [--check-data] [--explain-opt]\n       \
+                 cargo run graph <filename> [--dot|--json] [--reorder]\n\
+                 Targets: {}", registry.describe());
+    let (graph, filename, flags) = match args {
+        [_, command, filename, flags @ ..] if command == "graph" => (true, filename, flags),
+        [_, filename, flags @ ..] if !flags.is_empty() => (false, filename, flags),
+        _ => return Err(usage),
+    };
+
+    let mut format = graph::Format::Dot;
+    let mut target = None;
+    let mut reorder = false;
+    let mut deny_warnings = false;
+    let mut check_data = false;
+    let mut explain_optimizations = false;
+    let mut standalone = false;
+    let mut dialect = None;
+    let mut target_options = Vec::new();
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        match flag.as_str() {
+            "-s" if !graph && target.is_none() => target = Some("scheme"),
+            "-p" if !graph && target.is_none() => target = Some("prolog"),
+            "--target" if !graph && target.is_none() => target = Some(flags.next().ok_or("Expected a target name after --target")?),
+            "--dot" if graph => format = graph::Format::Dot,
+            "--json" if graph => format = graph::Format::Json,
+            "--reorder" => reorder = true,
+            "--deny-warnings" => deny_warnings = true,
+            "--check-data" => check_data = true,
+            "--explain-opt" => explain_optimizations = true,
+            "--standalone" if !graph => standalone = true,
+            "--dialect" if !graph => dialect = Some(flags.next().ok_or("Expected a dialect name after --dialect")?),
+            _ => {
+                // --<target>-<option> <value>
+                let option = registry.names().into_iter().find_map(|name| {
+                    let option = flag.strip_prefix("--")?.strip_prefix(name)?.strip_prefix('-')?;
+                    Some((name, option))
+                });
+                match option {
+                    Some((name, option)) if !graph => {
+                        let value = flags.next().ok_or_else(|| format!("Expected a value after {}", flag))?;
+                        target_options.push((flag, name, option, value));
+                    },
+                    _ => return Err(format!("Invalid flag '{}'. Use --target <name>, -s for Scheme or -p for Prolog.", flag)),
+                }
+            },
+        }
     }
 
-    let filename = &args[1];
-    let flag = &args[2];
+    let command = if graph {
+        Command::Graph(format)
+    } else {
+        let Some(target) = target else {
+            return Err(usage);
+        };
+        let mut backend = registry.take(target)?;
+        // the dialect only changes the header of a self-contained program, each language has its own
+        match dialect {
+            Some(_) if !standalone => return Err("--dialect requires --standalone".to_string()),
+            Some(dialect) => backend.configure("dialect", dialect)?,
+            None => {},
+        }
+        for (flag, name, option, value) in target_options {
+            if name != backend.name() {
+                return Err(format!("{} is only available for the {} target", flag, name));
+            }
+            backend.configure(option, value)?;
+        }
+        Command::Generate(backend)
+    };
+
+    Ok(Options { filename: filename.clone(), command, standalone, reorder, deny_warnings, check_data, explain_optimizations })
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
 
-    // check for propper 3rd arg
-    let is_scheme_output = match flag.as_str() {
-        "-s" => true,
-        "-p" => false,
-        _ => {
-            println!("Invalid flag. Use -s for Scheme or -p for Prolog.");
+    // check for propper arguments
+    let options = match parse_arguments(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}", e);
             return;
         }
     };
 
-    let content = fs::read_to_string(filename).expect("Failed to read file");
+    let content = fs::read_to_string(&options.filename).expect("Failed to read file");
 
     // call lexical analysis
     let tokens_parsed = match lexer::lexical_analysis(&content) {
@@ -47,9 +133,10 @@ fn main() {
 
     // call syntactical analysis
     let mut parser = parser::Parser::new(tokens_parsed);
-    let tokens_parsed = match parser.parse() {
+    let mut tokens_parsed = match parser.parse() {
         Ok(nodes) => {
-            println!("Lexical and Syntax analysis passed");
+            // the generated code and the graphs are redirected to a file, so they must be the only thing written to the standard output
+            eprintln!("Lexical and Syntax analysis passed");
             nodes
         },
         Err(e) => {
@@ -58,28 +145,109 @@ fn main() {
         }
     };
 
-    // output the desired language
-    if is_scheme_output {
-        let scheme_output = tokens_parsed.iter()
-            .map(|node| scheme::convert_to_scheme(node))
-            .collect::<Vec<String>>()
-            .join("\n");
+    // call semantic analysis - warnings do not stop the compilation
+    let (symbols, diagnostics) = semantic::analyze(&tokens_parsed);
+    let mut has_errors = false;
+    for diagnostic in &diagnostics {
+        match diagnostic.severity {
+            semantic::Severity::Error => {
+                println!("Semantic error: {}", diagnostic);
+                has_errors = true;
+            },
+            semantic::Severity::Warning => eprintln!("Warning: {}", diagnostic),
+        }
+    }
+    if has_errors {
+        return;
+    }
 
-        println!("{}", scheme_output);
-    } else {
-        let prolog_statements = tokens_parsed.iter()
-            .map(|node| prolog::convert_to_prolog(node))
-            .filter(|s| !s.is_empty())  // Filter out empty strings
-            .collect::<Vec<String>>()
-            .join(",\n   ");
-        
-        // Remove trailing comma if present and format the output
-        let prolog_output = if prolog_statements.ends_with(",") {
-            format!("main :-\n   {}.", &prolog_statements[..prolog_statements.len()-1])
-        } else {
-            format!("main :-\n   {}.", prolog_statements)
-        };
-        
-        println!("{}", prolog_output);
+    // the process section may be sorted by its dependencies before checking the order of definitions
+    if options.reorder {
+        if let Err(e) = dataflow::reorder(&mut tokens_parsed) {
+            println!("Dataflow error: {}", e);
+            return;
+        }
+    }
+    let dataflow_errors = dataflow::check(&tokens_parsed);
+    for diagnostic in &dataflow_errors {
+        println!("Dataflow error: {}", diagnostic);
+    }
+    if !dataflow_errors.is_empty() {
+        return;
+    }
+
+    // check the expressions against the declared types
+    let types = types::TypeEnv::from_symbols(&symbols);
+    let type_errors = types.check(&tokens_parsed);
+    for diagnostic in &type_errors {
+        println!("Type error: {}", diagnostic);
+    }
+    if !type_errors.is_empty() {
+        return;
+    }
+
+    if let Command::Graph(format) = options.command {
+        println!("{}", graph::build(&tokens_parsed).render(format));
+        return;
+    }
+
+    // run the lint rules with the levels configured for the project
+    let mut lint_config = match lint::LintConfig::from_file(&Path::new(&options.filename).with_file_name(lint::CONFIG_FILE)) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("Lint configuration error: {}", e);
+            return;
+        }
+    };
+    if options.deny_warnings {
+        lint_config.deny_warnings();
+    }
+    for diagnostic in lint::run(&tokens_parsed, &lint_config) {
+        match diagnostic.severity {
+            semantic::Severity::Error => {
+                println!("Lint error: {}", diagnostic);
+                has_errors = true;
+            },
+            semantic::Severity::Warning => eprintln!("Warning: {}", diagnostic),
+        }
+    }
+    if has_errors {
+        return;
+    }
+
+    // the files are looked up relative to the program
+    if options.check_data {
+        let directory = Path::new(&options.filename).parent().unwrap_or(Path::new("."));
+        let data_errors = datacheck::check(&tokens_parsed, directory);
+        for diagnostic in &data_errors {
+            println!("Data error: {}", diagnostic);
+        }
+        if !data_errors.is_empty() {
+            return;
+        }
+    }
+
+    // output the desired language from the optimized intermediate representation
+    let mut program = ir::lower(&tokens_parsed, &types);
+    let explanations = match optimize::optimize(&mut program) {
+        Ok(explanations) => explanations,
+        Err(errors) => {
+            for diagnostic in &errors {
+                println!("Arithmetic error: {}", diagnostic);
+            }
+            return;
+        }
+    };
+    for explanation in explanations {
+        if options.explain_optimizations {
+            eprintln!("Optimization: {}", explanation);
+        }
+    }
+    if let Command::Generate(backend) = &options.command {
+        for warning in backend.diagnostics(&program) {
+            eprintln!("Warning: {}", warning);
+        }
+        let name = Path::new(&options.filename).file_stem().map_or("program".into(), |stem| stem.to_string_lossy());
+        println!("{}", backend.generate(&program, options.standalone, &name));
     }
 }
@@ -0,0 +1,151 @@
+/*
+    This module names the values of the program in the generated code. Every target has a table of reserved words:
+    its keywords and the procedures the generated code and its runtime define or call. A DA variable whose name is
+    reserved gets a prefix DA identifiers cannot contain (they are only made of letters), so no name clashes
+    and the DA name is found back by removing the prefix
+*/
+
+use crate::builtins;
+use crate::ir::{Program, Operand, number_to_string};
+
+// How a target language names variables and temporaries
+pub struct Mangler {
+    variable_prefix: &'static str, // added to every variable, e.g. Prolog variables must start with a capital letter
+    escape: &'static str, // added to the variables whose name is reserved
+    temporary_prefix: &'static str, // followed by the number of the temporary, so temporaries never look like DA names
+    reserved: &'static [&'static str], // names of the builtin functions are always reserved
+}
+
+impl Mangler {
+    pub fn variable(&self, name: &str) -> String {
+        let name = format!("{}{}", self.variable_prefix, name);
+        if self.reserved.contains(&name.as_str()) || builtins::lookup(&name).is_some() {
+            format!("{}{}", self.escape, name)
+        } else {
+            name
+        }
+    }
+
+    pub fn temporary(&self, number: usize) -> String {
+        format!("{}{}", self.temporary_prefix, number)
+    }
+
+    pub fn operand(&self, operand: &Operand) -> String {
+        match operand {
+            Operand::Variable(name, _) => self.variable(name),
+            Operand::Temporary(number, _) => self.temporary(*number),
+            Operand::Constant(value) => number_to_string(*value),
+        }
+    }
+
+    // Warnings for the variables of the program written under another name than their DA name
+    pub fn renamed(&self, program: &Program, language: &str) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for target in program.instructions.iter().flat_map(|instruction| instruction.targets()) {
+            if let Operand::Variable(name, _) = target {
+                let written = self.variable(name);
+                if written != format!("{}{}", self.variable_prefix, name) {
+                    warnings.push(format!("Variable '{}' is named '{}' in the generated {} because '{}' is reserved", name, written, language, name));
+                }
+            }
+        }
+        warnings
+    }
+}
+
+pub const SCHEME: Mangler = Mangler {
+    variable_prefix: "",
+    escape: "da-",
+    temporary_prefix: "t",
+    reserved: &[
+        // syntax
+        "and", "begin", "case", "cond", "define", "delay", "do", "else", "guard", "if", "import", "include",
+        "lambda", "let", "letrec", "library", "or", "parameterize", "quasiquote", "quote", "require", "set", "unless",
+        "unquote", "when",
+        // procedures called by the generated code, its runtime and the dialect headers, and the ones they define
+        "abs", "append", "apply", "assq", "car", "cdr", "cons", "display", "error", "exact", "exit", "expt", "length",
+        "list", "map", "max", "min", "newline", "not", "quotient", "remainder", "reverse", "round", "slice", "sqrt",
+        "string", "values", "vector", "write",
+        // parameter of the row filters of 'where' clauses
+        "col",
+    ],
+};
+
+// Variables are prefixed with 'V' and temporaries with 'T', so they never clash with the variables
+// the generated lambdas introduce (X, Y and Z) nor with atoms - no name needs to be reserved
+pub const PROLOG: Mangler = Mangler {
+    variable_prefix: "V",
+    escape: "V_",
+    temporary_prefix: "T",
+    reserved: &[],
+};
+
+pub const PYTHON: Mangler = Mangler {
+    variable_prefix: "",
+    escape: "da_",
+    temporary_prefix: "t",
+    reserved: &[
+        // keywords, including the soft ones
+        "and", "as", "assert", "async", "await", "break", "case", "class", "continue", "def", "del", "elif", "else",
+        "except", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda", "match", "nonlocal",
+        "not", "or", "pass", "raise", "return", "try", "type", "while", "with", "yield",
+        // functions and modules used by the generated code and its runtime
+        "abs", "any", "csv", "dict", "float", "format", "int", "isinstance", "json", "len", "list", "max", "min",
+        "namedtuple", "np", "open", "pd", "print", "statistics", "str", "sum", "tuple", "zip",
+        // parameter of the row filters of 'where' clauses
+        "col",
+    ],
+};
+
+// R finds functions even when a variable has their name, the functions are only reserved to keep the code readable
+pub const R: Mangler = Mangler {
+    variable_prefix: "",
+    escape: "da_",
+    temporary_prefix: "t",
+    reserved: &[
+        // reserved words
+        "break", "else", "for", "function", "if", "in", "next", "repeat", "while",
+        // functions used by the generated code and its runtime
+        "c", "cat", "coef", "cor", "format", "formatC", "length", "list", "lm", "local", "max", "min", "names", "paste",
+        "sd", "sink", "slice", "unlist", "unname",
+        // file loaded by a read with a 'where' clause, its filter looks at the columns of the table
+        "table",
+    ],
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_names_get_the_escape_prefix() {
+        assert_eq!(SCHEME.variable("xval"), "xval");
+        assert_eq!(SCHEME.variable("list"), "da-list");
+        assert_eq!(PYTHON.variable("format"), "da_format");
+        assert_eq!(PYTHON.variable("lambda"), "da_lambda");
+        assert_eq!(R.variable("table"), "da_table");
+        assert_eq!(R.variable("xval"), "xval");
+    }
+
+    #[test]
+    fn builtin_functions_are_always_reserved() {
+        assert_eq!(SCHEME.variable("mean"), "da-mean");
+        assert_eq!(PYTHON.variable("correlation"), "da_correlation");
+        assert_eq!(R.variable("describe"), "da_describe");
+    }
+
+    #[test]
+    fn procedures_of_the_scheme_runtime_are_reserved() {
+        // output-fixed and the exact of the Racket header
+        for name in ["exact", "expt", "round", "abs", "quotient", "remainder"] {
+            assert_eq!(SCHEME.variable(name), format!("da-{}", name));
+        }
+    }
+
+    #[test]
+    fn prolog_variables_are_prefixed() {
+        assert_eq!(PROLOG.variable("xval"), "Vxval");
+        assert_eq!(PROLOG.variable("mean"), "Vmean");
+        assert_eq!(PROLOG.temporary(3), "T3");
+    }
+}
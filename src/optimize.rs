@@ -0,0 +1,276 @@
+/*
+    This module optimizes the intermediate representation before code is generated:
+    constant arithmetic is computed at compile time, repeated builtin calls are computed once,
+    computations the output section does not use are removed and a file read several times is loaded only once
+*/
+
+use crate::parser::Operator;
+use crate::ir::{Program, Instruction, Operand};
+use crate::types::Type;
+use crate::builtins::Function;
+use crate::semantic::Diagnostic;
+
+// Runs every pass and returns a description of each change made to the program, or the divisions by zero it found
+pub fn optimize(program: &mut Program) -> Result<Vec<String>, Vec<Diagnostic>> {
+    let mut explanations = fold_constants(program)?;
+    explanations.extend(eliminate_common_calls(program));
+    explanations.extend(eliminate_dead_code(program));
+    explanations.extend(share_loads(program));
+    Ok(explanations)
+}
+
+// Replaces arithmetic between constants by its result, and every use of a constant value by the constant itself.
+// A division whose divisor folds to 0 is reported, as it would fail (or give infinities) in every target
+fn fold_constants(program: &mut Program) -> Result<Vec<String>, Vec<Diagnostic>> {
+    let mut explanations = Vec::new();
+    let mut constants: Vec<(Operand, f64)> = Vec::new();
+    let mut divisions_by_zero = Vec::new();
+
+    for (position, instruction) in program.instructions.iter_mut().enumerate() {
+        for operand in instruction.operands_mut() {
+            substitute(operand, &constants);
+        }
+        if let Instruction::Binary(_, Operator::Divide, _, Operand::Constant(divisor)) = instruction {
+            if *divisor == 0.0 {
+                divisions_by_zero.push(position);
+            }
+        }
+        let folded = match instruction {
+            Instruction::Binary(target, operator, Operand::Constant(left), Operand::Constant(right)) => {
+                evaluate(*operator, *left, *right).map(|value| (target.clone(), value))
+            },
+            _ => None,
+        };
+        if let Some((target, value)) = folded {
+            if let Operand::Variable(..) = target {
+                explanations.push(format!("Folded {} to {}", instruction, Operand::Constant(value)));
+            }
+            *instruction = Instruction::Copy(target, Operand::Constant(value));
+        }
+        if let Instruction::Copy(target, Operand::Constant(value)) = instruction {
+            constants.push((target.clone(), *value));
+        }
+    }
+
+    for operand in program.output_operands_mut() {
+        substitute(operand, &constants);
+    }
+
+    if !divisions_by_zero.is_empty() {
+        return Err(divisions_by_zero.into_iter().map(|position| division_by_zero(&program.instructions, position)).collect());
+    }
+    Ok(explanations)
+}
+
+// The instructions of an assignment end with the one computing the variable, so the first variable assigned from
+// the division on is the one whose value divides by zero; there is none after the values of the output section
+fn division_by_zero(instructions: &[Instruction], position: usize) -> Diagnostic {
+    let variable = instructions[position..].iter().flat_map(Instruction::targets).find_map(|target| match target {
+        Operand::Variable(name, _) => Some(name),
+        _ => None,
+    });
+    let message = match variable {
+        Some(name) => format!("Division by zero when computing '{}'", name),
+        None => "Division by zero in the output section".to_string(),
+    };
+    Diagnostic::error(message, None)
+}
+
+// Result of an arithmetic operation between two numbers (division by zero is reported by fold_constants)
+fn evaluate(operator: Operator, left: f64, right: f64) -> Option<f64> {
+    match operator {
+        Operator::Add => Some(left + right),
+        Operator::Subtract => Some(left - right),
+        Operator::Multiply => Some(left * right),
+        Operator::Divide if right != 0.0 => Some(left / right),
+        Operator::Divide => None,
+    }
+}
+
+fn substitute(operand: &mut Operand, constants: &[(Operand, f64)]) {
+    if let Some((_, value)) = constants.iter().find(|(known, _)| known == operand) {
+        *operand = Operand::Constant(*value);
+    }
+}
+
+// Builtin functions have no side effects, so a call repeated with the same arguments reuses the results of the first one.
+// Repeated calls computing temporaries are removed and their uses replaced, the ones computing variables become copies
+fn eliminate_common_calls(program: &mut Program) -> Vec<String> {
+    let mut explanations = Vec::new();
+    let mut calls: Vec<(&'static str, Vec<Operand>, Vec<Operand>)> = Vec::new(); // function, arguments and results
+    let mut replaced: Vec<(Operand, Operand)> = Vec::new();
+
+    let mut kept = Vec::new();
+    for mut instruction in program.instructions.drain(..) {
+        for operand in instruction.operands_mut() {
+            replace(operand, &replaced);
+        }
+        let Instruction::Call(targets, Function::Builtin(builtin), args) = &instruction else {
+            kept.push(instruction);
+            continue;
+        };
+        let Some((_, _, results)) = calls.iter().find(|(name, known, _)| *name == builtin.name && known == args) else {
+            calls.push((builtin.name, args.clone(), targets.clone()));
+            kept.push(instruction);
+            continue;
+        };
+
+        let names = results.iter().map(|result| result.to_string()).collect::<Vec<String>>();
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+        explanations.push(format!("Reused {} = {}({}) instead of computing it again", names.join(", "), builtin.name, args.join(", ")));
+        for (target, result) in targets.iter().zip(results) {
+            match target {
+                Operand::Temporary(..) => replaced.push((target.clone(), result.clone())),
+                _ => kept.push(Instruction::Copy(target.clone(), result.clone())),
+            }
+        }
+    }
+    program.instructions = kept;
+
+    for operand in program.output_operands_mut() {
+        replace(operand, &replaced);
+    }
+    explanations
+}
+
+fn replace(operand: &mut Operand, replaced: &[(Operand, Operand)]) {
+    if let Some((_, value)) = replaced.iter().find(|(known, _)| known == operand) {
+        *operand = value.clone();
+    }
+}
+
+// Removes the instructions whose results are never used, directly or indirectly, by the output section
+fn eliminate_dead_code(program: &mut Program) -> Vec<String> {
+    let mut explanations = Vec::new();
+    let mut live: Vec<Operand> = program.output_operands().into_iter().cloned().collect();
+
+    // uses always come after definitions, so walking backwards finds every live value before its definition
+    let mut kept = Vec::new();
+    for instruction in program.instructions.drain(..).rev() {
+        if instruction.targets().iter().any(|target| live.contains(target)) {
+            live.extend(instruction.operands().into_iter().cloned());
+            kept.push(instruction);
+        } else if let Instruction::Copy(Operand::Variable(..), Operand::Constant(_)) = instruction {
+            explanations.push(format!("Removed {}, its uses were replaced by the constant", instruction));
+        } else if instruction.targets().iter().any(|target| matches!(target, Operand::Variable(..))) {
+            explanations.push(format!("Removed {}, its value is not used by the output section", instruction));
+        }
+    }
+    kept.reverse();
+    program.instructions = kept;
+
+    explanations.reverse();
+    explanations
+}
+
+// Files read more than once are loaded as a table by their first read, the reads then extract their column from it
+fn share_loads(program: &mut Program) -> Vec<String> {
+    let mut explanations = Vec::new();
+    let mut files: Vec<(&str, bool)> = Vec::new();
+    for instruction in &program.instructions {
+        if let Instruction::Read(_, file, header, ..) = instruction {
+            files.push((file, *header));
+        }
+    }
+    let mut shared: Vec<(String, bool)> = Vec::new();
+    for &(file, header) in &files {
+        let repeated = files.iter().filter(|&&other| other == (file, header)).count() > 1;
+        if repeated && !shared.iter().any(|(known, known_header)| known == file && *known_header == header) {
+            shared.push((file.to_string(), header));
+        }
+    }
+
+    for (file, header) in shared {
+        let table = program.new_temporary(Type::Table);
+        let mut names = Vec::new();
+        let mut first = None;
+        for (position, instruction) in program.instructions.iter_mut().enumerate() {
+            let Instruction::Read(target, read_file, read_header, column, filter) = instruction else {
+                continue;
+            };
+            if *read_file != file || *read_header != header {
+                continue;
+            }
+            names.push(target.to_string());
+            first.get_or_insert(position);
+            *instruction = Instruction::Extract(target.clone(), table.clone(), column.clone(), filter.take());
+        }
+        if let Some(position) = first {
+            program.instructions.insert(position, Instruction::LoadTable(table, file.clone(), header));
+        }
+        explanations.push(format!("Shared one load of {} between {}", file, names.join(", ")));
+    }
+    explanations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer, parser, semantic, ir};
+    use crate::types::TypeEnv;
+
+    // Instructions of the optimized program, as text, and the explanations of the changes (or the errors)
+    fn optimized(input: &str, process: &str, output: &str) -> Result<(Vec<String>, Vec<String>), Vec<String>> {
+        let source = format!("data: x : vector, y : vector, v : vector, k : number, a : number, b : number \
+            input: {} process: {} output: {} end.", input, process, output);
+        let nodes = parser::Parser::new(lexer::lexical_analysis(&source).unwrap()).parse().unwrap();
+        let types = TypeEnv::from_symbols(&semantic::analyze(&nodes).0);
+        let mut program = ir::lower(&nodes, &types);
+        match optimize(&mut program) {
+            Ok(explanations) => Ok((program.instructions.iter().map(Instruction::to_string).collect(), explanations)),
+            Err(errors) => Err(errors.into_iter().map(|error| error.message).collect()),
+        }
+    }
+
+    const READ_X: &str = "x = read(\"a.csv\", true, 0)";
+
+    #[test]
+    fn constants_are_folded_and_propagated() {
+        let (instructions, explanations) = optimized(READ_X, "k = 2 * 3, v = x * (k + 1)", "v").unwrap();
+        assert_eq!(instructions, ["x = read(\"a.csv\", true, 0)", "v = x * 7"]);
+        assert_eq!(explanations, ["Folded k = 2 * 3 to 6", "Removed k = 6, its uses were replaced by the constant"]);
+    }
+
+    #[test]
+    fn unused_computations_are_removed() {
+        let (instructions, explanations) = optimized(READ_X, "a = mean(x), b = stddev(x)", "b").unwrap();
+        assert_eq!(instructions, ["x = read(\"a.csv\", true, 0)", "b = stddev(x)"]);
+        assert_eq!(explanations, ["Removed a = mean(x), its value is not used by the output section"]);
+    }
+
+    #[test]
+    fn division_by_a_constant_zero_is_reported() {
+        assert_eq!(optimized(READ_X, "k = 2 * 3, v = x / (k - 6)", "v").unwrap_err(), ["Division by zero when computing 'v'"]);
+        assert_eq!(optimized(READ_X, "a = mean(x)", "a / 0").unwrap_err(), ["Division by zero in the output section"]);
+        assert!(optimized(READ_X, "k = 0, v = k / x", "v").is_ok());
+    }
+
+    #[test]
+    fn a_file_read_twice_is_loaded_once() {
+        let input = "x = read(\"a.csv\", true, 0), y = read(\"a.csv\", true, 1), v = read(\"a.csv\", false, 1)";
+        let (instructions, explanations) = optimized(input, "a = correlation(x, y), b = mean(v)", "a, b").unwrap();
+        assert_eq!(instructions, [
+            "t1 = load(\"a.csv\", true)",
+            "x = column(t1, 0)",
+            "y = column(t1, 1)",
+            "v = read(\"a.csv\", false, 1)",
+            "a = correlation(x, y)",
+            "b = mean(v)",
+        ]);
+        assert_eq!(explanations, ["Shared one load of a.csv between x, y"]);
+    }
+
+    #[test]
+    fn repeated_builtin_calls_are_computed_once() {
+        let (instructions, explanations) = optimized(READ_X, "v = x - mean(x), a = mean(x), b = stddev(x) / mean(x)", "v, a, b").unwrap();
+        assert_eq!(instructions, [
+            "x = read(\"a.csv\", true, 0)",
+            "t1 = mean(x)",
+            "v = x - t1",
+            "a = t1",
+            "t2 = stddev(x)",
+            "b = t2 / t1",
+        ]);
+        assert_eq!(explanations, ["Reused t1 = mean(x) instead of computing it again", "Reused t1 = mean(x) instead of computing it again"]);
+    }
+}
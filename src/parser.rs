@@ -4,37 +4,193 @@
     15 October 2023
 */
 
-use crate::lexer::Token;
+use crate::lexer::{self, Token, Span};
+use crate::types::Type;
 
 #[derive(Debug)]
 pub enum TreeNode {
+    Extern(Vec<ExternFunction>),
     Data(Vec<Declaration>),
     Input(Vec<Assignment>),
     Process(Vec<Assignment>),
-    Output(Vec<Expression>),
+    Output(Option<String>, Vec<Expression>), // optional file the section is written to instead of the console
     End,
 }
 
 #[derive(Debug)]
 pub enum Declaration {
-    Vector(String),
-    Number(String),
+    Vector(String, Span),
+    Number(String, Span),
+    Record(String, Span),
+}
+
+// Targets an extern function may be given a name for
+pub const EXTERN_TARGETS: [&str; 4] = ["scheme", "prolog", "python", "r"];
+
+// Function defined outside DA (in Scheme or Prolog code of the site) with its signature
+#[derive(Debug, Clone)]
+pub struct ExternFunction {
+    pub name: String,
+    pub span: Span,
+    pub parameters: Vec<Type>,
+    pub result: Type,
+    pub targets: Vec<(String, String)>, // target and name of the function in that target's code
+}
+
+impl ExternFunction {
+    // Name to call in the code of a target - the DA name unless the declaration gives another one
+    pub fn target_name(&self, target: &str) -> &str {
+        self.targets.iter()
+            .find(|(name, _)| name == target)
+            .map_or(self.name.as_str(), |(_, foreign)| foreign.as_str())
+    }
 }
 
 #[derive(Debug)]
 pub enum Assignment {
-    Assign(String, Expression),
+    Assign(String, Span, Expression),
+    Destructure(Vec<(String, Span)>, Expression), // several variables receiving the values of a tuple, e.g. a, b = regression(x, y)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
     Literal(LiteralValue), // For strings and numbers
-    Identifier(String),
+    Identifier(String, Span),
     FunctionCall(String, Vec<Expression>), // function name and arguments
-    
+    Comparison(Box<Expression>, Comparator, Box<Expression>), // row filter of a 'where' clause, e.g. col(3) > 100
+    VectorLiteral(Vec<Expression>), // e.g. [1, 2, 3]
+    Index(Box<Expression>, Box<Expression>), // vector and position, e.g. x[0]
+    Slice(Box<Expression>, Box<Expression>, Box<Expression>), // vector, start and (exclusive) end, e.g. x[1:10]
+    BinaryOp(Box<Expression>, Operator, Box<Expression>), // arithmetic, applied element-wise on vectors
+    Interpolation(Vec<Segment>), // output string with embedded values, e.g. "a = {a:.3}"
+    Formatted(Box<Expression>, u32), // output value with a number of decimals, e.g. a:.3
+    Field(Box<Expression>, String), // field of a record, e.g. s.mean
 }
 
-#[derive(Debug)]
+// Piece of an interpolated output string
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Text(String),
+    Value(Expression, Option<u32>), // value and optional number of decimals
+}
+
+impl Assignment {
+    // Variables assigned by the operation, with the position where they are assigned
+    pub fn targets(&self) -> Vec<(&str, Span)> {
+        match self {
+            Assignment::Assign(name, span, _) => vec![(name.as_str(), *span)],
+            Assignment::Destructure(names, _) => names.iter().map(|(name, span)| (name.as_str(), *span)).collect(),
+        }
+    }
+
+    // Expression computing the assigned value(s)
+    pub fn expression(&self) -> &Expression {
+        match self {
+            Assignment::Assign(_, _, expr) | Assignment::Destructure(_, expr) => expr,
+        }
+    }
+}
+
+impl Expression {
+    // Variables read by the expression, with the position where they are used
+    pub fn identifiers(&self) -> Vec<(&str, Span)> {
+        let mut identifiers = Vec::new();
+        self.collect_identifiers(&mut identifiers);
+        identifiers
+    }
+
+    fn collect_identifiers<'a>(&'a self, identifiers: &mut Vec<(&'a str, Span)>) {
+        match self {
+            Expression::Literal(_) => {},
+            Expression::Identifier(name, span) => identifiers.push((name, *span)),
+            Expression::FunctionCall(_, args) | Expression::VectorLiteral(args) => {
+                for arg in args {
+                    arg.collect_identifiers(identifiers);
+                }
+            },
+            Expression::Comparison(left, _, right) | Expression::BinaryOp(left, _, right) | Expression::Index(left, right) => {
+                left.collect_identifiers(identifiers);
+                right.collect_identifiers(identifiers);
+            },
+            Expression::Slice(vector, start, end) => {
+                vector.collect_identifiers(identifiers);
+                start.collect_identifiers(identifiers);
+                end.collect_identifiers(identifiers);
+            },
+            Expression::Interpolation(segments) => {
+                for segment in segments {
+                    if let Segment::Value(value, _) = segment {
+                        value.collect_identifiers(identifiers);
+                    }
+                }
+            },
+            Expression::Formatted(value, _) | Expression::Field(value, _) => value.collect_identifiers(identifiers),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl Operator {
+    // Every target writes the arithmetic operators the same way
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Subtract => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparator {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparator {
+    // Symbol of the comparator in DA, also used by Python and R (Scheme and Prolog write equality differently)
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Comparator::Less => "<",
+            Comparator::LessEqual => "<=",
+            Comparator::Greater => ">",
+            Comparator::GreaterEqual => ">=",
+            Comparator::Equal => "==",
+            Comparator::NotEqual => "!=",
+        }
+    }
+}
+
+// Format of a file written by the output section, chosen from its extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileFormat {
+    Csv,
+    Json,
+    Text,
+}
+
+pub fn file_format(file: &str) -> FileFormat {
+    let extension = file.rsplit_once('.').map(|(_, extension)| extension.to_lowercase());
+    match extension.as_deref() {
+        Some("csv") => FileFormat::Csv,
+        Some("json") => FileFormat::Json,
+        _ => FileFormat::Text,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum LiteralValue {
     Str(String),
     Num(u32),
@@ -44,6 +200,7 @@ pub enum LiteralValue {
 // this will be our parse tree - provides methods for parsing
 pub struct Parser {
     tokens: Vec<Token>,
+    spans: Vec<Span>, // position of each token
     current: usize,
 }
 
@@ -51,16 +208,26 @@ pub struct Parser {
 impl Parser {
 
     // Constructor to initialize the parser with tokens
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+    pub fn new(tokens: Vec<(Token, Span)>) -> Self {
+        let (tokens, spans) = tokens.into_iter().unzip();
+        Parser { tokens, spans, current: 0 }
     }
 
-    // main function to parse tokens into tree nodes
+    // main function to parse tokens into tree nodes - errors report the position of the token where they were found
     pub fn parse(&mut self) -> Result<Vec<TreeNode>, String> {
+        self.parse_program().map_err(|e| match self.spans.get(self.current) {
+            Some(span) => format!("{} at {}", e, span),
+            None => format!("{} at end of input", e),
+        })
+    }
+
+    fn parse_program(&mut self) -> Result<Vec<TreeNode>, String> {
         let mut nodes = Vec::new();
         
         while self.current < self.tokens.len() {
-            if self.check_and_advance_token(Token::DATA) {
+            if self.check_and_advance_token(Token::EXTERN) {
+                nodes.push(self.parse_extern()?);
+            } else if self.check_and_advance_token(Token::DATA) {
                 nodes.push(self.parse_data()?);
             } else if self.check_and_advance_token(Token::INPUT) {
                 nodes.push(self.parse_input()?);
@@ -83,6 +250,11 @@ impl Parser {
         Ok(nodes)
     }
 
+    // Position of the current token (or of the last one once all tokens are consumed)
+    fn current_span(&self) -> Span {
+        self.spans.get(self.current).or(self.spans.last()).copied().unwrap_or(Span { line: 1, column: 1 })
+    }
+
     // Checks if current Token matches the expected and advances current by 1
     fn check_and_advance_token(&mut self, expected: Token) -> bool {
         if self.current >= self.tokens.len() {
@@ -96,6 +268,78 @@ impl Parser {
         }
     }
 
+    // Parses the "extern" section ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    fn parse_extern(&mut self) -> Result<TreeNode, String> {
+        if !self.check_and_advance_token(Token::COLON) {
+            return Err("Expected ':' after extern".to_string());
+        }
+
+        let mut functions = Vec::new();
+        while self.current < self.tokens.len() && self.tokens[self.current] != Token::DATA {
+            functions.push(self.parse_extern_function()?);
+
+            // If there's no comma, we don't expect another declaration
+            if !self.check_and_advance_token(Token::COMMA) {
+                break;
+            }
+        }
+        Ok(TreeNode::Extern(functions))
+    }
+
+    // Parses a signature followed by the names in each target, e.g. winsorize(vector, number) : vector prolog "winsorize_list"
+    fn parse_extern_function(&mut self) -> Result<ExternFunction, String> {
+        let Some(Token::ID(name)) = self.tokens.get(self.current).cloned() else {
+            return Err("Expected function name in extern declaration".to_string());
+        };
+        let span = self.current_span();
+        self.current += 1;  // Move past the identifier
+
+        if !self.check_and_advance_token(Token::LPAREN) {
+            return Err(format!("Expected '(' after extern function {}", name));
+        }
+        let mut parameters = Vec::new();
+        while !self.check_and_advance_token(Token::RPAREN) {
+            parameters.push(self.parse_type()?);
+            if !self.check_and_advance_token(Token::COMMA) && self.tokens.get(self.current) != Some(&Token::RPAREN) {
+                return Err(format!("Expected ',' or ')' in parameters of {}", name));
+            }
+        }
+
+        if !self.check_and_advance_token(Token::COLON) {
+            return Err(format!("Expected ':' and return type after parameters of {}", name));
+        }
+        let result = self.parse_type()?;
+
+        // optional names in the generated code, e.g. scheme "site-winsorize"
+        let mut targets = Vec::new();
+        while let Some(Token::ID(target)) = self.tokens.get(self.current).cloned() {
+            if !EXTERN_TARGETS.contains(&target.as_str()) {
+                return Err(format!("Unknown target '{}' for extern function {} (expected {})", target, name, EXTERN_TARGETS.join(", ")));
+            }
+            self.current += 1;  // Move past the target
+            let Some(Token::STRING(foreign)) = self.tokens.get(self.current).cloned() else {
+                return Err(format!("Expected STRING name after '{}'", target));
+            };
+            self.current += 1;  // Move past the name
+            targets.push((target, foreign));
+        }
+
+        Ok(ExternFunction { name, span, parameters, result, targets })
+    }
+
+    // Parses the type of a parameter or of a returned value
+    fn parse_type(&mut self) -> Result<Type, String> {
+        if self.check_and_advance_token(Token::VECTOR) {
+            Ok(Type::Vector)
+        } else if self.check_and_advance_token(Token::NUMBER) {
+            Ok(Type::Number)
+        } else if self.check_and_advance_token(Token::RECORD) {
+            Ok(Type::Record)
+        } else {
+            Err(format!("Expected a type, found {:?}", self.tokens.get(self.current)))
+        }
+    }
+
     // Parses the "data" section /////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
     fn parse_data(&mut self) -> Result<TreeNode, String> {
         // Ensure that the token after "DATA" is a colon.
@@ -116,6 +360,7 @@ impl Parser {
     // Parses declarations
     fn parse_declaration(&mut self) -> Result<Declaration, String> {
         if let Some(Token::ID(name)) = self.tokens.get(self.current).cloned() {
+            let span = self.current_span();
             self.current += 1;  // Move past the identifier
     
             if !self.check_and_advance_token(Token::COLON) {
@@ -124,19 +369,93 @@ impl Parser {
     
             if self.check_and_advance_token(Token::VECTOR) {
                 self.check_and_advance_token(Token::COMMA);  // Try to match comma and move past it if it's there
-                return Ok(Declaration::Vector(name));
+                return Ok(Declaration::Vector(name, span));
             } else if self.check_and_advance_token(Token::NUMBER) {
                 self.check_and_advance_token(Token::COMMA);  // Try to match comma and move past it if it's there
-                return Ok(Declaration::Number(name));
+                return Ok(Declaration::Number(name, span));
+            } else if self.check_and_advance_token(Token::RECORD) {
+                self.check_and_advance_token(Token::COMMA);  // Try to match comma and move past it if it's there
+                return Ok(Declaration::Record(name, span));
             }
             // Handle assignment case if needed.
         }
         Err("Invalid declaration".to_string())
     }    
     
-    // Parses expressions
+    // Parses expressions - additive operators bind the loosest
     fn parse_expression(&mut self) -> Result<Expression, String> {
-        let current_token = self.tokens[self.current].clone();
+        let mut expression = self.parse_term()?;
+
+        loop {
+            let operator = match self.tokens.get(self.current) {
+                Some(Token::PLUS) => Operator::Add,
+                Some(Token::MINUS) => Operator::Subtract,
+                _ => break,
+            };
+            self.current += 1;  // Move past the operator
+            let right = self.parse_term()?;
+            expression = Expression::BinaryOp(Box::new(expression), operator, Box::new(right));
+        }
+        Ok(expression)
+    }
+
+    // Parses multiplicative terms
+    fn parse_term(&mut self) -> Result<Expression, String> {
+        let mut expression = self.parse_postfix()?;
+
+        loop {
+            let operator = match self.tokens.get(self.current) {
+                Some(Token::TIMES) => Operator::Multiply,
+                Some(Token::DIVIDE) => Operator::Divide,
+                _ => break,
+            };
+            self.current += 1;  // Move past the operator
+            let right = self.parse_postfix()?;
+            expression = Expression::BinaryOp(Box::new(expression), operator, Box::new(right));
+        }
+        Ok(expression)
+    }
+
+    // Parses indexing x[i], slicing x[from:to] and field access s.name applied to a primary expression
+    fn parse_postfix(&mut self) -> Result<Expression, String> {
+        let mut expression = self.parse_primary()?;
+
+        loop {
+            if self.check_and_advance_token(Token::PERIOD) {
+                let field = match self.tokens.get(self.current) {
+                    Some(Token::ID(name)) => name.clone(),
+                    other => return Err(format!("Expected field name after '.', found {:?}", other)),
+                };
+                self.current += 1;  // Move past the field name
+                expression = Expression::Field(Box::new(expression), field);
+                continue;
+            }
+
+            if !self.check_and_advance_token(Token::LBRACKET) {
+                break;
+            }
+            let start = self.parse_expression()?;
+
+            expression = if self.check_and_advance_token(Token::COLON) {
+                let end = self.parse_expression()?;
+                Expression::Slice(Box::new(expression), Box::new(start), Box::new(end))
+            } else {
+                Expression::Index(Box::new(expression), Box::new(start))
+            };
+
+            if !self.check_and_advance_token(Token::RBRACKET) {
+                return Err("Expected ']' after index".to_string());
+            }
+        }
+        Ok(expression)
+    }
+
+    // Parses literals, identifiers, function calls, vector literals and parenthesized expressions
+    fn parse_primary(&mut self) -> Result<Expression, String> {
+        let current_token = match self.tokens.get(self.current) {
+            Some(token) => token.clone(),
+            None => return Err("Unexpected end of input, expected an expression".to_string()),
+        };
         
         // Check for literals first
         match current_token {
@@ -156,11 +475,37 @@ impl Parser {
                 self.current += 1;
                 return Ok(Expression::Literal(LiteralValue::Bool(false)));
             }
+            // vector literal - a list of expressions between brackets
+            Token::LBRACKET => {
+                self.current += 1;
+                let mut elements = Vec::new();
+
+                while !self.check_and_advance_token(Token::RBRACKET) {
+                    elements.push(self.parse_expression()?);
+
+                    if !self.check_and_advance_token(Token::COMMA) && self.tokens.get(self.current) != Some(&Token::RBRACKET) {
+                        return Err(format!("Expected ',' or ']' in vector literal, found {:?}", self.tokens.get(self.current)));
+                    }
+                }
+                return Ok(Expression::VectorLiteral(elements));
+            }
+            // parenthesized expression
+            Token::LPAREN => {
+                self.current += 1;
+                let expression = self.parse_expression()?;
+                if !self.check_and_advance_token(Token::RPAREN) {
+                    return Err("Expected ')' after expression".to_string());
+                }
+                return Ok(expression);
+            }
             _ => {} // if none of the previous, do nothing and continue
         }
         
         // Now check for identifiers and function calls
         if let Token::ID(name) = &current_token {
+            let span = self.current_span();
+            self.current += 1;  // Move past the identifier
+
             if self.check_and_advance_token(Token::LPAREN) {
                 let mut arguments = Vec::new();
     
@@ -168,56 +513,17 @@ impl Parser {
                 while !self.check_and_advance_token(Token::RPAREN) {
                     arguments.push(self.parse_expression()?);
     
-                    if !self.check_and_advance_token(Token::COMMA) && self.tokens[self.current] != Token::RPAREN {
-                        return Err(format!("Expected ',' or ')' in function arguments, found {:?}", self.tokens[self.current]));
+                    if !self.check_and_advance_token(Token::COMMA) && self.tokens.get(self.current) != Some(&Token::RPAREN) {
+                        return Err(format!("Expected ',' or ')' in function arguments, found {:?}", self.tokens.get(self.current)));
                     }
                 }
                 return Ok(Expression::FunctionCall(name.clone(), arguments));
             } else {
-                self.current += 1;
-                return Ok(Expression::Identifier(name.clone()));
-            }
-        } else if current_token == Token::REGRESSIONA 
-                || current_token == Token::REGRESSIONB 
-                || current_token == Token::CORRELATION
-                || current_token == Token::MEAN 
-                || current_token == Token::STDDEV {
-            let function_name = match current_token {
-                Token::REGRESSIONA => "REGRESSIONA",
-                Token::REGRESSIONB => "REGRESSIONB",
-                Token::CORRELATION => "CORRELATION",
-                Token::MEAN => "MEAN",
-                Token::STDDEV => "STDDEV",
-                _ => unreachable!(),  // we should never hit this case based on our condition
-            };
-    
-            self.current += 1;  // Move past the function name
-    
-            if self.check_and_advance_token(Token::LPAREN) {
-                let mut arguments = Vec::new();
-    
-                // Expect one argument for mean and stddev, two for the others
-                let expected_args = if function_name == "MEAN" || function_name == "STDDEV" { 1 } else { 2 };
-    
-                for i in 0..expected_args {
-                    arguments.push(self.parse_expression()?);
-    
-                    if i < expected_args - 1 && !self.check_and_advance_token(Token::COMMA) {
-                        return Err(format!("Expected ',' after argument of {}", function_name));
-                    }
-                }
-    
-                if !self.check_and_advance_token(Token::RPAREN) {
-                    return Err(format!("Expected ')' after arguments of {}", function_name));
-                }
-    
-                return Ok(Expression::FunctionCall(function_name.to_string(), arguments));
-            } else {
-                return Err(format!("Expected '(' after {}", function_name));
+                return Ok(Expression::Identifier(name.clone(), span));
             }
         }
     
-        Err(format!("Invalid expression for token: {:?}", self.tokens[self.current]))
+        Err(format!("Invalid expression for token: {:?}", current_token))
     }
     
 
@@ -244,6 +550,7 @@ impl Parser {
     fn parse_input_op(&mut self) -> Result<Assignment, String> {
         // make sure that the token is an ID - if so, make a copy of the Token to work with
         if let Some(Token::ID(name)) = self.tokens.get(self.current).cloned() {
+            let span = self.current_span();
             self.current += 1;  // Move past the identifier
     
             // make sure the next Token is an '=' and advance iterator if so
@@ -262,11 +569,14 @@ impl Parser {
             }
     
             // make sure the next Token is a STRING - if so, assign the value to prompt
-            let prompt = if let Token::STRING(s) = &self.tokens[self.current] {
-                self.current += 1;
-                s.clone()
-            } else {
-                return Err("Expected STRING argument for 'read' function".to_string());
+            let prompt = match self.tokens.get(self.current) {
+                Some(Token::STRING(s)) => {
+                    let prompt = s.clone();
+                    self.current += 1;
+                    prompt
+                },
+                Some(_) => return Err("Expected STRING argument for 'read' function".to_string()),
+                None => return Err("Unexpected end of input, expected STRING argument for 'read' function".to_string()),
             };
 
             // make sure the next Token is a COMMA
@@ -288,36 +598,60 @@ impl Parser {
                 return Err("Expected ',' in input operation".to_string());
             }
     
-            // make sure the next Token is a NUM - if so, assign to length
-            let length = if let Token::NUM(n) = self.tokens[self.current] {
-                self.current += 1;
-                n
-            } else {
-                return Err("Expected NUM argument for 'read' function".to_string());
+            // the column is given by its position, or by its name when the file has a header
+            let column = match self.tokens.get(self.current).cloned() {
+                Some(Token::NUM(n)) => LiteralValue::Num(n),
+                Some(Token::STRING(name)) if echo => LiteralValue::Str(name),
+                Some(Token::STRING(_)) => return Err("Columns can only be read by name from a file with a header".to_string()),
+                _ => return Err("Expected NUM or STRING column argument for 'read' function".to_string()),
             };
+            self.current += 1;
             
-            // make sure the next Token is a '(' and advance iterator if so
+            // make sure the next Token is a ')' and advance iterator if so
             if !self.check_and_advance_token(Token::RPAREN) {
                 return Err("Expected ')' after 'read' arguments".to_string());
             }
     
             // store all 3 values into the FunctionCall variant of the Expression enum
-            let read_expr = Expression::FunctionCall(
-                "read".to_string(),
-                vec![
-                    Expression::Literal(LiteralValue::Str(prompt)),
-                    Expression::Literal(LiteralValue::Bool(echo)),
-                    Expression::Literal(LiteralValue::Num(length)),
-                ],
-            );
+            let mut read_args = vec![
+                Expression::Literal(LiteralValue::Str(prompt)),
+                Expression::Literal(LiteralValue::Bool(echo)),
+                Expression::Literal(column),
+            ];
+
+            // an optional 'where' clause restricts the rows that are loaded - it is kept as a 4th argument
+            if self.check_and_advance_token(Token::WHERE) {
+                read_args.push(self.parse_comparison()?);
+            }
+
+            let read_expr = Expression::FunctionCall("read".to_string(), read_args);
     
             // store all of this into Assignment enum
-            Ok(Assignment::Assign(name, read_expr))
+            Ok(Assignment::Assign(name, span, read_expr))
         } else {
             Err("Invalid input operation".to_string())
         }
     }
     
+    // Parses a row filter of the form EXPRESSION COMPARATOR EXPRESSION (e.g. col(3) > 100)
+    fn parse_comparison(&mut self) -> Result<Expression, String> {
+        let left = self.parse_expression()?;
+
+        let comparator = match self.tokens.get(self.current) {
+            Some(Token::LT) => Comparator::Less,
+            Some(Token::LE) => Comparator::LessEqual,
+            Some(Token::GT) => Comparator::Greater,
+            Some(Token::GE) => Comparator::GreaterEqual,
+            Some(Token::EQ) => Comparator::Equal,
+            Some(Token::NE) => Comparator::NotEqual,
+            other => return Err(format!("Expected comparison operator in 'where' clause, found {:?}", other)),
+        };
+        self.current += 1;  // Move past the comparison operator
+
+        let right = self.parse_expression()?;
+        Ok(Expression::Comparison(Box::new(left), comparator, Box::new(right)))
+    }
+
     // Parses the process section /////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
     fn parse_process(&mut self) -> Result<TreeNode, String> {
         
@@ -342,7 +676,18 @@ impl Parser {
     fn parse_process_op(&mut self) -> Result<Assignment, String> {
         // make sure that the token is an ID - if so, make a copy of the Token to work with
         if let Some(Token::ID(name)) = self.tokens.get(self.current).cloned() {
+            let mut names = vec![(name, self.current_span())];
             self.current += 1;  // Move past the identifier
+
+            // a list of identifiers 'a, b = ...' receives the values of a tuple
+            while self.check_and_advance_token(Token::COMMA) {
+                if let Some(Token::ID(next_name)) = self.tokens.get(self.current).cloned() {
+                    names.push((next_name, self.current_span()));
+                    self.current += 1;
+                } else {
+                    return Err("Expected identifier in multiple assignment".to_string());
+                }
+            }
     
             // make sure the next Token is an '=' and advance iterator if so
             if !self.check_and_advance_token(Token::ASSIGN) {
@@ -352,8 +697,13 @@ impl Parser {
             // Parse the right-hand expression which represents a function call or some computation
             let rhs_expression = self.parse_expression()?;  // Assuming the parse_expression function handles function calls too
     
-            // store the variable and its expression into Assignment enum
-            Ok(Assignment::Assign(name, rhs_expression))
+            // store the variable(s) and the expression into Assignment enum
+            if names.len() == 1 {
+                let (name, span) = names.remove(0);
+                Ok(Assignment::Assign(name, span, rhs_expression))
+            } else {
+                Ok(Assignment::Destructure(names, rhs_expression))
+            }
         } else {
             Err("Invalid process operation".to_string())
         }
@@ -362,6 +712,18 @@ impl Parser {
     // Parses the output section /////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
     fn parse_output(&mut self) -> Result<TreeNode, String> {
             
+        // An optional 'to STRING' redirects the section to a file
+        let target = if self.check_and_advance_token(Token::TO) {
+            if let Some(Token::STRING(file)) = self.tokens.get(self.current) {
+                self.current += 1;
+                Some(file.clone())
+            } else {
+                return Err("Expected file name after 'output to'".to_string());
+            }
+        } else {
+            None
+        };
+
         // Ensure that the token after "OUTPUT" is a colon
         if !self.check_and_advance_token(Token::COLON) {
             return Err("Expected ':' after OUTPUT".to_string());
@@ -370,14 +732,258 @@ impl Parser {
         let mut expressions = Vec::new();
         while self.current < self.tokens.len() {
             // Parse the expression and push it to our list of expressions
-            expressions.push(self.parse_expression()?);
+            let span = self.current_span();
+            let expression = match self.parse_expression()? {
+                // strings with '{...}' placeholders are interpolated
+                Expression::Literal(LiteralValue::Str(s)) if s.contains('{') || s.contains('}') => parse_interpolation(&s, span)?,
+                expression => expression,
+            };
+
+            if let Expression::FunctionCall(name, args) = &expression {
+                if name == "write" {
+                    check_write_arguments(args)?;
+                }
+            }
+
+            // CSV and JSON files hold named values, so only variables can be sent to them
+            let structured_target = target.as_deref().map(file_format).is_some_and(|format| format != FileFormat::Text);
+            if structured_target && !matches!(expression, Expression::Identifier(..)) {
+                return Err("Only variables can be written to a CSV or JSON output file".to_string());
+            }
+
+            // an optional format spec ':.N' sets the number of decimals to display
+            if self.check_and_advance_token(Token::COLON) {
+                expressions.push(Expression::Formatted(Box::new(expression), self.parse_precision()?));
+            } else {
+                expressions.push(expression);
+            }
     
             // If there's no comma, we don't expect another expression, so break out of the loop
             if !self.check_and_advance_token(Token::COMMA) {
                 break;
             }
         }
-        Ok(TreeNode::Output(expressions))
+        Ok(TreeNode::Output(target, expressions))
+    }
+
+    // Parses the '.N' part of a format spec and returns N
+    fn parse_precision(&mut self) -> Result<u32, String> {
+        if !self.check_and_advance_token(Token::PERIOD) {
+            return Err("Expected '.' in format spec".to_string());
+        }
+        if let Some(Token::NUM(n)) = self.tokens.get(self.current) {
+            self.current += 1;
+            Ok(*n)
+        } else {
+            Err("Expected number of decimals in format spec".to_string())
+        }
+    }
+}
+
+// Checks the arguments of write(FILE, ID, ...): a CSV or JSON file name followed by the variables to save
+fn check_write_arguments(args: &[Expression]) -> Result<(), String> {
+    match args.split_first() {
+        Some((Expression::Literal(LiteralValue::Str(file)), values)) => {
+            if file_format(file) == FileFormat::Text {
+                return Err(format!("write() only supports .csv and .json files, found \"{}\"", file));
+            }
+            if values.is_empty() || !values.iter().all(|value| matches!(value, Expression::Identifier(..))) {
+                return Err("write() expects a file name followed by one or more variables".to_string());
+            }
+            Ok(())
+        },
+        _ => Err("write() expects a file name followed by one or more variables".to_string()),
+    }
+}
+
+// Splits an output string into text and '{name}' or '{name:.N}' placeholders ('{{' and '}}' stand for braces)
+// placeholders are given the position of the string
+fn parse_interpolation(text: &str, span: Span) -> Result<Expression, String> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                // collect everything up to the closing brace
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err(format!("Unclosed '{{' in output string \"{}\"", text)),
+                    }
+                }
+
+                let (name, precision) = match placeholder.split_once(':') {
+                    Some((name, spec)) => {
+                        let precision = spec.strip_prefix('.')
+                            .and_then(|digits| digits.parse::<u32>().ok())
+                            .ok_or_else(|| format!("Invalid format spec '{}' in output string", spec))?;
+                        (name.trim(), Some(precision))
+                    }
+                    None => (placeholder.trim(), None),
+                };
+
+                // a placeholder is a variable optionally followed by record fields, e.g. {s.mean}
+                let mut parts = name.split('.');
+                let mut value = Expression::Identifier(parts.next().unwrap_or_default().to_string(), span);
+                for field in parts {
+                    value = Expression::Field(Box::new(value), field.to_string());
+                }
+                if !name.split('.').all(lexer::is_identifier) {
+                    return Err(format!("Invalid variable name '{}' in output string", name));
+                }
+
+                if !literal.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut literal)));
+                }
+                segments.push(Segment::Value(value, precision));
+            }
+            '}' => return Err(format!("Unmatched '}}' in output string \"{}\"", text)),
+            _ => literal.push(ch),
+        }
     }
+
+    if !literal.is_empty() {
+        segments.push(Segment::Text(literal));
+    }
+    Ok(Expression::Interpolation(segments))
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<Vec<TreeNode>, String> {
+        Parser::new(lexer::lexical_analysis(source)?).parse()
+    }
+
+    // Expression assigned by the only read() of a program
+    fn read_arguments(source: &str) -> Vec<Expression> {
+        let nodes = parse(source).unwrap();
+        let Some(TreeNode::Input(assignments)) = nodes.iter().find(|node| matches!(node, TreeNode::Input(_))) else {
+            panic!("no input section in {:?}", nodes);
+        };
+        match assignments[0].expression() {
+            Expression::FunctionCall(name, args) if name == "read" => args.clone(),
+            other => panic!("expected a read() call, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn where_clause_is_kept_as_fourth_read_argument() {
+        let args = read_arguments("data: x : vector input: x = read(\"a.csv\", true, 1) where col(3) * 2 >= 100 end.");
+        assert_eq!(args.len(), 4);
+        let Expression::Comparison(left, Comparator::GreaterEqual, right) = &args[3] else {
+            panic!("expected a comparison, found {:?}", args[3]);
+        };
+        assert!(matches!(**left, Expression::BinaryOp(_, Operator::Multiply, _)));
+        assert_eq!(**right, Expression::Literal(LiteralValue::Num(100)));
+    }
+
+    #[test]
+    fn read_without_where_has_three_arguments() {
+        let args = read_arguments("data: x : vector input: x = read(\"a.csv\", false, 2) end.");
+        assert_eq!(args.len(), 3);
+    }
+
+    #[test]
+    fn where_clause_needs_a_comparison() {
+        let error = parse("data: x : vector input: x = read(\"a.csv\", true, 1) where col(1) end.").unwrap_err();
+        assert!(error.starts_with("Expected comparison operator in 'where' clause"), "{}", error);
+    }
+
+    // Expression assigned by the first operation of the process section
+    fn process_expression(process: &str) -> Expression {
+        let nodes = parse(&format!("data: x : vector, y : vector input: x = read(\"a.csv\", true, 1) process: {} end.", process)).unwrap();
+        let Some(TreeNode::Process(assignments)) = nodes.into_iter().find(|node| matches!(node, TreeNode::Process(_))) else {
+            panic!("no process section");
+        };
+        assignments[0].expression().clone()
+    }
+
+    fn number(n: u32) -> Box<Expression> {
+        Box::new(Expression::Literal(LiteralValue::Num(n)))
+    }
+
+    #[test]
+    fn multiplication_binds_tighter_than_addition() {
+        let Expression::BinaryOp(left, Operator::Subtract, right) = process_expression("y = x - 2 * 3") else {
+            panic!("expected a subtraction");
+        };
+        assert!(matches!(*left, Expression::Identifier(ref name, _) if name == "x"));
+        assert_eq!(*right, Expression::BinaryOp(number(2), Operator::Multiply, number(3)));
+    }
+
+    #[test]
+    fn vector_literals_indexes_and_slices() {
+        assert_eq!(process_expression("y = [1, 2, 3]"), Expression::VectorLiteral(vec![*number(1), *number(2), *number(3)]));
+        assert_eq!(process_expression("y = []"), Expression::VectorLiteral(Vec::new()));
+        assert!(matches!(process_expression("y = x[1:10]"), Expression::Slice(_, start, end) if start == number(1) && end == number(10)));
+        assert!(matches!(process_expression("y = x[0] * x"), Expression::BinaryOp(index, Operator::Multiply, _) if matches!(*index, Expression::Index(..))));
+    }
+
+    #[test]
+    fn unclosed_index_is_reported() {
+        let error = parse("data: x : vector, y : vector input: x = read(\"a.csv\", true, 1) process: y = x[1 end.").unwrap_err();
+        assert!(error.starts_with("Expected ']' after index"), "{}", error);
+    }
+
+    fn interpolation(text: &str) -> Result<Vec<Segment>, String> {
+        match parse_interpolation(text, Span { line: 1, column: 1 })? {
+            Expression::Interpolation(segments) => Ok(segments),
+            other => panic!("expected an interpolation, found {:?}", other),
+        }
+    }
+
+    fn variable(name: &str) -> Expression {
+        Expression::Identifier(name.to_string(), Span { line: 1, column: 1 })
+    }
+
+    #[test]
+    fn interpolation_splits_text_and_placeholders() {
+        assert_eq!(interpolation("a = {myA:.2}, s = {s.mean} {{n}}").unwrap(), [
+            Segment::Text("a = ".to_string()),
+            Segment::Value(variable("myA"), Some(2)),
+            Segment::Text(", s = ".to_string()),
+            Segment::Value(Expression::Field(Box::new(variable("s")), "mean".to_string()), None),
+            Segment::Text(" {n}".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn interpolation_rejects_invalid_placeholders() {
+        assert!(interpolation("{a").unwrap_err().starts_with("Unclosed '{'"));
+        assert!(interpolation("a}").unwrap_err().starts_with("Unmatched '}'"));
+        assert_eq!(interpolation("{a:2}").unwrap_err(), "Invalid format spec '2' in output string");
+        assert_eq!(interpolation("{1a}").unwrap_err(), "Invalid variable name '1a' in output string");
+        assert_eq!(interpolation("{}").unwrap_err(), "Invalid variable name '' in output string");
+    }
+
+    #[test]
+    fn output_values_take_a_number_of_decimals() {
+        let nodes = parse("data: x : vector, a : number input: x = read(\"a.csv\", true, 1) process: a = mean(x) output: a:.3, \"{a}\" end.").unwrap();
+        let Some(TreeNode::Output(None, expressions)) = nodes.iter().find(|node| matches!(node, TreeNode::Output(..))) else {
+            panic!("no output section");
+        };
+        assert!(matches!(&expressions[0], Expression::Formatted(value, 3) if matches!(**value, Expression::Identifier(ref name, _) if name == "a")));
+        assert!(matches!(&expressions[1], Expression::Interpolation(segments) if segments.len() == 1));
+    }
+
+    #[test]
+    fn end_of_input_inside_read_is_reported() {
+        let error = parse("data: x : vector input: x = read(").unwrap_err();
+        assert_eq!(error, "Unexpected end of input, expected STRING argument for 'read' function at end of input");
+    }
+}
@@ -1,103 +1,511 @@
-/* 
-    This module translates our AST (Abstract Syntax Tree) into its Prolog representation.
+/*
+    This module translates our IR (Intermediate Representation) into its Prolog representation.
     Chris Kendall
     15 October 2023
 */
 
-use crate::parser::{TreeNode, Declaration, Assignment, Expression, LiteralValue};
+use crate::parser::{Expression, LiteralValue, Comparator, FileFormat, file_format};
+use crate::ir::{Program, Instruction, Operand, Column, OutputSection, OutputOp, Piece, number_to_string};
+use crate::types::Type;
+use crate::names;
+use crate::backend::{self, Backend};
 
-// Convert a TreeNode into its Prolog representation
-pub fn convert_to_prolog(node: &TreeNode) -> String {
-    node.to_prolog()
+// Runtime defining every predicate the generated goals call (CSV loading, builtins, output files)
+const PRELUDE: &str = include_str!("runtime/prelude.pl");
+
+// Lambdas are only built in SWI-Prolog (library(yall)), the other dialects call them through these clauses
+const LAMBDAS: &str = "'>>'(Params, Lambda, A1) :- copy_term(Params-Lambda, [A1]-Goal), call(Goal).
+'>>'(Params, Lambda, A1, A2) :- copy_term(Params-Lambda, [A1, A2]-Goal), call(Goal).
+'>>'(Params, Lambda, A1, A2, A3) :- copy_term(Params-Lambda, [A1, A2, A3]-Goal), call(Goal).";
+
+// Prolog system a self-contained program is written for
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Dialect {
+    #[default]
+    Swi,
+    Gnu,
+    Scryer,
 }
 
-// Trait to define behavior for converting to Prolog
-trait ToProlog {
-    fn to_prolog(&self) -> String;
+impl Dialect {
+    pub const NAMES: [&'static str; 3] = ["swi", "gnu", "scryer"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "swi" => Some(Dialect::Swi),
+            "gnu" => Some(Dialect::Gnu),
+            "scryer" => Some(Dialect::Scryer),
+            _ => None,
+        }
+    }
+
+    // Directives placed before the prelude: the module, the libraries and the predicates missing from the system
+    fn header(self, module: &str, exports: &str) -> String {
+        match self {
+            Dialect::Swi => format!(":- module({}, {}).\n\
+                :- use_module(library(lists)).\n\
+                :- use_module(library(apply)).\n\
+                :- use_module(library(yall)).", module, exports),
+            // no module system: the program is loaded in the user space, and the missing predicates are only
+            // added when the system does not define them (asserting a builtin raises a permission error)
+            Dialect::Gnu => format!("% GNU Prolog has no modules, {} is loaded in the user space\n\
+                :- set_prolog_flag(double_quotes, atom).\n\
+                :- catch(assertz((writeln(Term) :- write(Term), nl)), _, true).\n\
+                :- catch(assertz((tell(File) :- open(File, write, Stream), set_output(Stream))), _, true).\n\
+                :- catch(assertz((told :- current_output(Stream), close(Stream), set_output(user_output))), _, true).\n\
+                {}", module, LAMBDAS),
+            // double-quoted text is a list of characters, so writeln/1 writes those lists as text
+            Dialect::Scryer => format!(":- module({}, {}).\n\
+                :- use_module(library(lists)).\n\
+                :- use_module(library(format)).\n\
+                writeln(Term) :- ( Term = [_|_], maplist(atom, Term) -> atom_chars(Atom, Term), write(Atom) ; write(Term) ), nl.\n\
+                tell(File) :- open(File, write, Stream), set_output(Stream).\n\
+                told :- current_output(Stream), close(Stream), set_output(user_output).\n\
+                {}", module, exports, LAMBDAS),
+        }
+    }
+}
+
+// How text is written in the generated terms. Double-quoted text is a string in SWI-Prolog but a list of codes
+// or characters in other systems depending on the double_quotes flag, so the representation is chosen explicitly
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TextMode {
+    Atom, // 'text'
+    #[default]
+    String, // "text"
+    Codes, // [116, 101, 120, 116], displayed with format/2 and ~s
 }
 
-// Implement the ToProlog trait for TreeNode type
-impl ToProlog for TreeNode {
-    fn to_prolog(&self) -> String {
+impl TextMode {
+    pub const NAMES: [&'static str; 3] = ["atom", "string", "codes"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "atom" => Some(TextMode::Atom),
+            "string" => Some(TextMode::String),
+            "codes" => Some(TextMode::Codes),
+            _ => None,
+        }
+    }
+
+    // Term holding a piece of text
+    fn literal(self, text: &str) -> String {
         match self {
-            // If the node is a data declaration, nothing to convert for Prolog
-            TreeNode::Data(_) => String::new(),
-            // Convert each assignment in the input section to Prolog and concatenate
-            TreeNode::Input(assignments) => {
-                assignments.iter().map(|a| a.to_prolog()).collect::<Vec<String>>().join(",\n   ")
+            TextMode::Atom => quoted(text, '\''),
+            TextMode::String => quoted(text, '"'),
+            TextMode::Codes => format!("[{}]", text.chars().map(|c| (c as u32).to_string()).collect::<Vec<String>>().join(", ")),
+        }
+    }
+
+    // open/3 takes the name of a file as an atom (SWI-Prolog also accepts a string), never as a list of codes
+    fn file(self, name: &str) -> String {
+        match self {
+            TextMode::String => quoted(name, '"'),
+            TextMode::Atom | TextMode::Codes => quoted(name, '\''),
+        }
+    }
+}
+
+// Quoted atom or string (other characters are written as UTF-8)
+fn quoted(text: &str, quote: char) -> String {
+    backend::quoted(text, quote, |code| format!("\\x{:x}\\", code))
+}
+
+// Shape of the generated program
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Layout {
+    #[default]
+    Main, // a main clause running every goal in order
+    Queries, // one ?- query per line displayed on the console and per output file, computing the values it uses
+    KnowledgeBase, // a value(Name, Value) fact or rule per variable, and a main clause displaying them
+}
+
+impl Layout {
+    pub const NAMES: [&'static str; 3] = ["main", "queries", "kb"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "main" => Some(Layout::Main),
+            "queries" => Some(Layout::Queries),
+            "kb" => Some(Layout::KnowledgeBase),
+            _ => None,
+        }
+    }
+}
+
+// Prolog code for SWI-Prolog unless --dialect chooses another system; --prolog-text and --prolog-layout
+// set how text is written and the shape of the code
+#[derive(Default)]
+pub struct PrologBackend {
+    dialect: Dialect,
+    text: TextMode,
+    layout: Layout,
+}
+
+impl Backend for PrologBackend {
+    fn name(&self) -> &'static str {
+        "prolog"
+    }
+
+    fn file_extension(&self) -> &'static str {
+        "pl"
+    }
+
+    fn configure(&mut self, option: &str, value: &str) -> Result<(), String> {
+        match option {
+            "dialect" => {
+                self.dialect = Dialect::from_name(value)
+                    .ok_or_else(|| format!("Unknown Prolog dialect '{}', expected one of: {}", value, Dialect::NAMES.join(", ")))?;
             },
-            // Convert each assignment in the process section to Prolog and concatenate
-            TreeNode::Process(assignments) => assignments.iter().map(|a| a.to_prolog()).collect::<Vec<String>>().join(",\n   "),
-            // Convert each output expression to a writeln call in Prolog
-            TreeNode::Output(exprs) => {
-                exprs.iter().map(|e| {
-                    match e {
-                        Expression::Literal(LiteralValue::Str(_)) => format!("writeln({})", e.to_prolog()),
-                        _ => format!("writeln({})", e.to_prolog())
-                    }
-                }).collect::<Vec<String>>().join(",\n   ")
-            },            
-            TreeNode::End => "".to_string(),
+            "text" => {
+                self.text = TextMode::from_name(value)
+                    .ok_or_else(|| format!("Unknown Prolog text mode '{}', expected one of: {}", value, TextMode::NAMES.join(", ")))?;
+            },
+            "layout" => {
+                self.layout = Layout::from_name(value)
+                    .ok_or_else(|| format!("Unknown Prolog layout '{}', expected one of: {}", value, Layout::NAMES.join(", ")))?;
+            },
+            _ => return Err(format!("The prolog target has no option '{}'", option)),
         }
+        Ok(())
+    }
+
+    fn generate(&self, program: &Program, standalone: bool, name: &str) -> String {
+        if standalone {
+            standalone_program(program, self.dialect, self.layout, self.text, name)
+        } else {
+            convert_to_prolog(program, self.layout, self.text)
+        }
+    }
+
+    fn diagnostics(&self, program: &Program) -> Vec<String> {
+        names::PROLOG.renamed(program, "Prolog")
     }
 }
 
-// Implement the ToProlog trait for Declaration type. (no Prolog rep for this so return empty string)
-impl ToProlog for Declaration {
-    fn to_prolog(&self) -> String {
-        String::new()
+// A complete program for a dialect: the module named after the DA program, the runtime prelude,
+// then the main clause, run once the file is loaded. Queries become initialization directives run in order instead
+pub fn standalone_program(program: &Program, dialect: Dialect, layout: Layout, text: TextMode, name: &str) -> String {
+    let module = module_name(name);
+    match layout {
+        Layout::Queries => {
+            let directives = queries(program, text).iter()
+                .map(|goals| format!(":- initialization((\n   {})).", goals.join(",\n   ")))
+                .collect::<Vec<String>>();
+            format!("{}\n\n{}\n% ---- program ----\n\n{}", dialect.header(&module, "[]"), PRELUDE, directives.join("\n"))
+        },
+        Layout::Main | Layout::KnowledgeBase => {
+            format!("{}\n:- initialization(main).\n\n{}\n% ---- program ----\n\n{}", dialect.header(&module, "[main/0]"), PRELUDE, convert_to_prolog(program, layout, text))
+        },
     }
 }
 
-// Implement the ToProlog trait for Assignment type
-impl Assignment {
-    fn to_prolog(&self) -> String {
-        match self {
-            // Convert an assignment to its Prolog representation
-            Assignment::Assign(name, expr) => {
-                match expr {
-                    // If the expression is a function call, convert it to Prolog
-                    Expression::FunctionCall(func_name, args) => {
-                        let args_str: Vec<String> = args.iter().map(|arg| arg.to_prolog()).collect();
-                        match func_name.as_str() {
-                            // Special handling for "read" function (i.e. "load_data_column")
-                            "read" => {
-                                if let [file, false_val, col] = args_str.as_slice() {
-                                    return format!("load_data_column({}, {}, {}, V{})", file, false_val, col, name);
-                                }
-                                String::new() // If the match pattern isn't met, return an empty string
-                            },
-                            // use basic conversion for other functions
-                            _ => format!("{}({}, V{})", func_name.to_lowercase(), args_str.join(", "), name)
-                        }
-                    },
-                    _ => expr.to_prolog()  // directly produce the expression
+// Module names are atoms: lowercase letters, digits and underscores, starting with a letter
+fn module_name(name: &str) -> String {
+    let module = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect::<String>();
+    match module.chars().next() {
+        Some(first) if first.is_ascii_lowercase() => module,
+        _ => format!("da_{}", module),
+    }
+}
+
+// Convert a program into its Prolog representation in the given layout
+pub fn convert_to_prolog(program: &Program, layout: Layout, text: TextMode) -> String {
+    match layout {
+        Layout::Main => {
+            let used = program.instructions.iter().flat_map(Instruction::operands).chain(program.output_operands()).collect::<Vec<&Operand>>();
+            let mut goals = program.instructions.iter().map(|instruction| instruction_to_prolog(instruction, &used, text)).collect::<Vec<String>>();
+            goals.extend(program.outputs.iter().map(|section| output_section_to_prolog(section, text)));
+            clause("main", goals)
+        },
+        Layout::Queries => queries(program, text).iter()
+            .map(|goals| format!("?- {}.", goals.join(",\n   ")))
+            .collect::<Vec<String>>()
+            .join("\n"),
+        Layout::KnowledgeBase => knowledge_base(program, text),
+    }
+}
+
+// A fact when there are no goals, a rule otherwise
+fn clause(head: &str, mut goals: Vec<String>) -> String {
+    goals.retain(|goal| !goal.is_empty());
+    if goals.is_empty() {
+        format!("{}.", head)
+    } else {
+        format!("{} :-\n   {}.", head, goals.join(",\n   "))
+    }
+}
+
+// Goals of each query. Variables do not keep their values from one query to the next,
+// so each query computes again everything its output uses
+fn queries(program: &Program, text: TextMode) -> Vec<Vec<String>> {
+    let mut queries = Vec::new();
+    for section in &program.outputs {
+        // the lines of a file are written between a single tell/1 and told/0
+        let parts = match section.file {
+            Some(_) => vec![section.clone()],
+            None => section.operations.iter().map(|operation| OutputSection { file: None, operations: vec![operation.clone()] }).collect(),
+        };
+        for part in parts {
+            let mut goals = goals_computing(program, part.operands(), false, text);
+            goals.push(output_section_to_prolog(&part, text));
+            queries.push(goals);
+        }
+    }
+    queries
+}
+
+// Every variable is a value/2 fact when it is a constant and a rule otherwise, looking up the variables it uses,
+// and main/0 looks up the values displayed by the output section
+fn knowledge_base(program: &Program, text: TextMode) -> String {
+    let mut clauses = Vec::new();
+    for instruction in &program.instructions {
+        for target in instruction.targets() {
+            let Operand::Variable(name, _) = target else {
+                continue;
+            };
+            match instruction {
+                Instruction::Copy(_, Operand::Constant(value)) => clauses.push(format!("value({}, {}).", value_key(name), number_to_string(*value))),
+                _ => {
+                    let mut goals = goals_computing(program, instruction.operands(), true, text);
+                    goals.push(instruction_to_prolog(instruction, &[target], text));
+                    clauses.push(clause(&format!("value({}, {})", value_key(name), operand_to_prolog(target)), goals));
+                },
+            }
+        }
+    }
+    let mut goals = goals_computing(program, program.output_operands(), true, text);
+    goals.extend(program.outputs.iter().map(|section| output_section_to_prolog(section, text)));
+    clauses.push(clause("main", goals));
+    clauses.join("\n")
+}
+
+// Goals computing the operands, in program order. With lookup the variables are taken from
+// the knowledge base and only the temporaries are computed
+fn goals_computing(program: &Program, operands: Vec<&Operand>, lookup: bool, text: TextMode) -> Vec<String> {
+    let mut needed = operands;
+    let mut lookups = Vec::new();
+    let mut goals = Vec::new();
+    // uses always come after definitions, so walking backwards finds every needed value before its definition
+    for instruction in program.instructions.iter().rev() {
+        let targets = instruction.targets().into_iter().filter(|target| needed.contains(target)).collect::<Vec<&Operand>>();
+        if targets.is_empty() {
+            continue;
+        }
+        if lookup && targets.iter().all(|target| matches!(target, Operand::Variable(..))) {
+            for target in targets {
+                if let Operand::Variable(name, _) = target {
+                    lookups.push(format!("value({}, {})", value_key(name), operand_to_prolog(target)));
                 }
             }
+        } else {
+            goals.push(instruction_to_prolog(instruction, &needed, text));
+            needed.extend(instruction.operands());
         }
     }
+    lookups.reverse();
+    goals.reverse();
+    lookups.extend(goals);
+    lookups
 }
 
-// Implement the ToProlog trait for Expression type
-impl Expression {
-    fn to_prolog(&self) -> String {
-        match self {
-            // Convert literals to their Prolog representation
-            Expression::Literal(lit) => match lit {
-                LiteralValue::Str(s) => format!("\"{}\"", s),  
-                LiteralValue::Num(n) => n.to_string(),
-                LiteralValue::Bool(b) => if *b { "true" } else { "false" }.to_string(),
+// DA names are made of letters so they are atoms, but the ones that are Prolog operators must be quoted
+fn value_key(name: &str) -> String {
+    const OPERATORS: [&str; 13] = ["discontiguous", "div", "dynamic", "initialization", "is", "mod", "multifile", "public", "rdiv", "rem", "table", "volatile", "xor"];
+    if OPERATORS.contains(&name) {
+        quoted(name, '\'')
+    } else {
+        name.to_string()
+    }
+}
+
+// Goal computing an instruction, the result is the last argument of the predicates.
+// The values of a tuple missing from `used` are written as _, so the clause has no singleton variable
+fn instruction_to_prolog(instruction: &Instruction, used: &[&Operand], text: TextMode) -> String {
+    match instruction {
+        Instruction::Read(target, file, header, column, filter) => {
+            let (file, column) = (text.file(file), column_to_prolog(column, text));
+            match filter {
+                // a 'where' clause is passed as a condition term over col(N) for the loader to evaluate per row
+                Some(filter) => format!("load_data_column_where({}, {}, {}, {}, {})", file, header, column, filter_to_prolog(filter), operand_to_prolog(target)),
+                None => format!("load_data_column({}, {}, {}, {})", file, header, column, operand_to_prolog(target)),
+            }
+        },
+        // a file read several times is loaded once as a table, its columns are then taken from the table
+        Instruction::LoadTable(target, file, header) => format!("load_csv({}, {}, {})", text.file(file), header, operand_to_prolog(target)),
+        Instruction::Extract(target, table, column, filter) => {
+            let (table, column) = (operand_to_prolog(table), column_to_prolog(column, text));
+            match filter {
+                Some(filter) => format!("csv_column_where({}, {}, {}, {})", table, column, filter_to_prolog(filter), operand_to_prolog(target)),
+                None => format!("csv_column({}, {}, {})", table, column, operand_to_prolog(target)),
+            }
+        },
+        // functions returning several values bind them as a list
+        Instruction::Call(targets, function, args) => {
+            let args = args.iter().map(operand_to_prolog).collect::<Vec<String>>();
+            let result = match targets.as_slice() {
+                [target] => operand_to_prolog(target),
+                _ => {
+                    let values = targets.iter()
+                        .map(|target| if used.contains(&target) { operand_to_prolog(target) } else { "_".to_string() })
+                        .collect::<Vec<String>>();
+                    format!("[{}]", values.join(", "))
+                },
+            };
+            function.prolog_goal(&args, &result)
+        },
+        // Arithmetic is mapped over the elements with maplist when one of the operands is a vector
+        Instruction::Binary(target, operator, left, right) => {
+            let operator = operator.symbol();
+            let (result, left_term, right_term) = (operand_to_prolog(target), operand_to_prolog(left), operand_to_prolog(right));
+            match (left.ty() == Type::Vector, right.ty() == Type::Vector) {
+                (true, true) => format!("maplist([X, Y, Z]>>(Z is X {} Y), {}, {}, {})", operator, left_term, right_term, result),
+                (true, false) => format!("maplist([X, Z]>>(Z is X {} {}), {}, {})", operator, right_term, left_term, result),
+                (false, true) => format!("maplist([Y, Z]>>(Z is {} {} Y), {}, {})", left_term, operator, right_term, result),
+                (false, false) => format!("{} is {} {} {}", result, left_term, operator, right_term),
+            }
+        },
+        Instruction::Copy(target, value) => format!("{} = {}", operand_to_prolog(target), operand_to_prolog(value)),
+        // Vectors are Prolog lists
+        Instruction::Vector(target, elements) => {
+            let elements = elements.iter().map(operand_to_prolog).collect::<Vec<String>>();
+            format!("{} = [{}]", operand_to_prolog(target), elements.join(", "))
+        },
+        Instruction::Index(target, vector, index) => {
+            format!("nth0({}, {}, {})", operand_to_prolog(index), operand_to_prolog(vector), operand_to_prolog(target))
+        },
+        Instruction::Slice(target, vector, start, end) => {
+            format!("slice({}, {}, {}, {})", operand_to_prolog(vector), operand_to_prolog(start), operand_to_prolog(end), operand_to_prolog(target))
+        },
+        // records are lists of Field-Value pairs
+        Instruction::Field(target, record, field) => {
+            format!("memberchk({}-{}, {})", field, operand_to_prolog(target), operand_to_prolog(record))
+        },
+    }
+}
+
+// Goals of an output section - a text file receives what would have been displayed, between tell/1 and told/0
+fn output_section_to_prolog(section: &OutputSection, text: TextMode) -> String {
+    let mut goals = section.operations.iter().map(|operation| output_to_prolog(operation, text)).collect::<Vec<String>>();
+    if let Some(file) = &section.file {
+        goals.insert(0, format!("tell({})", text.file(file)));
+        goals.push("told".to_string());
+    }
+    goals.join(",\n   ")
+}
+
+// Converts one operation of the output section into the goal displaying it
+// formatted values and interpolated strings use format/2 so they are printed on a single line
+fn output_to_prolog(operation: &OutputOp, text: TextMode) -> String {
+    match operation {
+        OutputOp::Line(pieces) => match pieces.as_slice() {
+            // writeln/1 would display a list of codes as numbers
+            [Piece::Text(line)] if text == TextMode::Codes => format!("format({}, [{}])", text.literal("~s~n"), text.literal(line)),
+            [Piece::Text(line)] => format!("writeln({})", text.literal(line)),
+            [Piece::Value(value, None)] => format!("writeln({})", operand_to_prolog(value)),
+            _ => {
+                let mut directives = String::new();
+                let mut arguments = Vec::new();
+                for piece in pieces {
+                    match piece {
+                        Piece::Text(line) => directives.push_str(&line.replace('~', "~~")),
+                        Piece::Value(value, precision) => {
+                            directives.push_str(&format_directive(*precision));
+                            arguments.push(operand_to_prolog(value));
+                        }
+                    }
+                }
+                directives.push_str("~n");
+                format!("format({}, [{}])", text.literal(&directives), arguments.join(", "))
             },
-            // Convert identifiers (prefixing with 'V') for variables
-            Expression::Identifier(id) => format!("V{}", id),
-            // Convert function calls to Prolog (appending function name with 'V') for variables
-            Expression::FunctionCall(name, args) => {
-                let args_str = args.iter()
-                    .map(|arg| arg.to_prolog())
-                    .collect::<Vec<String>>()
-                    .join(", ");
-                format!("{}({}, V{})", name.to_lowercase(), args_str, name.to_lowercase())
-            }            
-        }
+        },
+        // Saves named values to a CSV (header and one row) or JSON (one object) file
+        OutputOp::Write(file, values) => {
+            let writer = if file_format(file) == FileFormat::Json { "write_json" } else { "write_csv" };
+            let names = values.iter().map(|(name, _)| text.literal(name)).collect::<Vec<String>>();
+            let values = values.iter().map(|(_, value)| operand_to_prolog(value)).collect::<Vec<String>>();
+            format!("{}({}, [{}], [{}])", writer, text.file(file), names.join(", "), values.join(", "))
+        },
+    }
+}
+
+fn operand_to_prolog(operand: &Operand) -> String {
+    names::PROLOG.operand(operand)
+}
+
+fn column_to_prolog(column: &Column, text: TextMode) -> String {
+    match column {
+        Column::Index(n) => n.to_string(),
+        Column::Name(name) => text.literal(name),
+    }
+}
+
+// format/2 directive for a displayed value: fixed decimals when a precision is given
+fn format_directive(precision: Option<u32>) -> String {
+    match precision {
+        Some(decimals) => format!("~{}f", decimals),
+        None => "~w".to_string(),
+    }
+}
+
+// Row filters are terms evaluated by the loader, so they are built without any goals (col(N) is kept as a plain term)
+fn filter_to_prolog(expr: &Expression) -> String {
+    match expr {
+        Expression::Comparison(left, comparator, right) => {
+            let operator = match comparator {
+                Comparator::Less => "<",
+                Comparator::LessEqual => "=<",
+                Comparator::Greater => ">",
+                Comparator::GreaterEqual => ">=",
+                Comparator::Equal => "=:=",
+                Comparator::NotEqual => "=\\=",
+            };
+            format!("{} {} {}", filter_to_prolog(left), operator, filter_to_prolog(right))
+        },
+        Expression::FunctionCall(_, args) => {
+            let args_str = args.iter().map(filter_to_prolog).collect::<Vec<String>>().join(", ");
+            format!("col({})", args_str)
+        },
+        Expression::BinaryOp(left, operator, right) => {
+            format!("({} {} {})", filter_to_prolog(left), operator.symbol(), filter_to_prolog(right))
+        },
+        Expression::Literal(LiteralValue::Num(n)) => n.to_string(),
+        _ => unreachable!("'where' filters only contain col(NUM), numbers and arithmetic"),
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer, parser, semantic, ir, optimize};
+    use crate::types::TypeEnv;
+
+    // Prolog code of a program computing a regression, displaying only the given variables
+    fn regression_to_prolog(output: &str, layout: Layout) -> String {
+        let source = format!("data: x : vector, y : vector, a : number, b : number \
+            input: x = read(\"a.csv\", true, 0), y = read(\"a.csv\", true, 1) process: a, b = regression(x, y) output: {} end.", output);
+        let nodes = parser::Parser::new(lexer::lexical_analysis(&source).unwrap()).parse().unwrap();
+        let types = TypeEnv::from_symbols(&semantic::analyze(&nodes).0);
+        let mut program = ir::lower(&nodes, &types);
+        optimize::optimize(&mut program).unwrap();
+        convert_to_prolog(&program, layout, TextMode::default())
+    }
+
+    #[test]
+    fn unused_values_of_a_tuple_are_anonymous() {
+        assert!(regression_to_prolog("a", Layout::Main).contains("regression(Vx, Vy, [Va, _])"));
+        assert!(regression_to_prolog("a, b", Layout::Main).contains("regression(Vx, Vy, [Va, Vb])"));
+        let queries = regression_to_prolog("a, b", Layout::Queries);
+        assert!(queries.contains("regression(Vx, Vy, [Va, _]),\n   writeln(Va)."), "{}", queries);
+        assert!(queries.contains("regression(Vx, Vy, [_, Vb]),\n   writeln(Vb)."), "{}", queries);
+    }
+
+    #[test]
+    fn knowledge_base_rules_only_name_their_own_value() {
+        let clauses = regression_to_prolog("a, b", Layout::KnowledgeBase);
+        assert!(clauses.contains("value(a, Va) :-\n   value(x, Vx),\n   value(y, Vy),\n   regression(Vx, Vy, [Va, _])."), "{}", clauses);
+        assert!(clauses.contains("value(b, Vb) :-\n   value(x, Vx),\n   value(y, Vy),\n   regression(Vx, Vy, [_, Vb])."), "{}", clauses);
+    }
+}
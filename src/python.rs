@@ -0,0 +1,309 @@
+/*
+    This module translates our IR (Intermediate Representation) into a Python script, loading the CSV files and
+    computing the statistics with the standard library or with pandas and numpy
+*/
+
+use crate::parser::{Expression, LiteralValue, FileFormat, file_format};
+use crate::ir::{Program, Instruction, Operand, Column, OutputSection, OutputOp, Piece, Inliner};
+use crate::types::Type;
+use crate::names;
+use crate::backend::{self, Backend};
+
+// Runtimes defining what the generated code calls besides the libraries (CSV loading, some builtins, output files)
+const PRELUDE: &str = include_str!("runtime/prelude.py");
+const PANDAS_PRELUDE: &str = include_str!("runtime/prelude_pandas.py");
+
+// Library the CSV files are loaded and the statistics computed with
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Library {
+    #[default]
+    Stdlib, // csv and statistics, vectors are lists
+    Pandas, // pandas and numpy, vectors are numpy arrays
+}
+
+impl Library {
+    pub const NAMES: [&'static str; 2] = ["stdlib", "pandas"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "stdlib" => Some(Library::Stdlib),
+            "pandas" => Some(Library::Pandas),
+            _ => None,
+        }
+    }
+
+    fn prelude(self) -> &'static str {
+        match self {
+            Library::Stdlib => PRELUDE,
+            Library::Pandas => PANDAS_PRELUDE,
+        }
+    }
+}
+
+// Python code using the standard library unless --python-library chooses pandas
+#[derive(Default)]
+pub struct PythonBackend {
+    library: Library,
+}
+
+impl Backend for PythonBackend {
+    fn name(&self) -> &'static str {
+        "python"
+    }
+
+    fn file_extension(&self) -> &'static str {
+        "py"
+    }
+
+    fn configure(&mut self, option: &str, value: &str) -> Result<(), String> {
+        match option {
+            "library" => {
+                self.library = Library::from_name(value)
+                    .ok_or_else(|| format!("Unknown Python library '{}', expected one of: {}", value, Library::NAMES.join(", ")))?;
+            },
+            "dialect" => return Err("The python target has no dialects, use --python-library to choose the library".to_string()),
+            _ => return Err(format!("The python target has no option '{}'", option)),
+        }
+        Ok(())
+    }
+
+    // The imports and the runtime are always included and the messages of the compiler go to the standard error,
+    // so the script written to the standard output runs as is even without --standalone, which only adds the shebang line
+    fn generate(&self, program: &Program, standalone: bool, _name: &str) -> String {
+        if standalone {
+            standalone_program(program, self.library)
+        } else {
+            script(program, self.library)
+        }
+    }
+
+    fn diagnostics(&self, program: &Program) -> Vec<String> {
+        names::PYTHON.renamed(program, "Python")
+    }
+}
+
+// A complete script started with a shebang line, so it can be made executable
+pub fn standalone_program(program: &Program, library: Library) -> String {
+    format!("#!/usr/bin/env python3\n{}", script(program, library))
+}
+
+// The runtime of the library with its imports, then the code of the program
+pub fn script(program: &Program, library: Library) -> String {
+    format!("{}\n# ---- program ----\n\n{}", library.prelude(), convert_to_python(program, library))
+}
+
+// convert a program into its Python representation
+pub fn convert_to_python(program: &Program, library: Library) -> String {
+    let mut converter = PythonConverter { library, inliner: Inliner::new(program) };
+    let mut lines = program.instructions.iter()
+        .filter_map(|instruction| converter.instruction(instruction))
+        .collect::<Vec<String>>();
+    lines.extend(program.outputs.iter().map(|section| converter.output_section(section)));
+    lines.join("\n")
+}
+
+// Temporaries chosen by the Inliner are written inside the expression using them, the others are assigned
+struct PythonConverter {
+    library: Library,
+    inliner: Inliner,
+}
+
+impl PythonConverter {
+    // Assignment of the values computed by an instruction (None when the value is inlined where it is used)
+    fn instruction(&mut self, instruction: &Instruction) -> Option<String> {
+        let numpy = self.library == Library::Pandas;
+        // operators must be put in parentheses when their value is inlined into another expression
+        let mut operation = false;
+        let value = match instruction {
+            Instruction::Read(_, file, header, column, filter) => {
+                let (file, column) = (string_literal(file), column_to_python(column));
+                let header = if *header { "True" } else { "False" };
+                match filter {
+                    // a 'where' clause becomes a row predicate; 'col' gives the value of a column for the current row
+                    Some(filter) => format!("read_csv({}, {}, {}, lambda col: {})", file, header, column, filter_to_python(filter)),
+                    None => format!("read_csv({}, {}, {})", file, header, column),
+                }
+            },
+            // a file read several times is loaded once as a table, its columns are then taken from the table
+            Instruction::LoadTable(_, file, header) => format!("load_csv({}, {})", string_literal(file), if *header { "True" } else { "False" }),
+            Instruction::Extract(_, table, column, filter) => match filter {
+                Some(filter) => format!("csv_column({}, {}, lambda col: {})", self.operand(table), column_to_python(column), filter_to_python(filter)),
+                None => format!("csv_column({}, {})", self.operand(table), column_to_python(column)),
+            },
+            Instruction::Call(_, function, args) => {
+                let args = args.iter().map(|arg| self.operand(arg)).collect::<Vec<String>>();
+                function.python_call(&args, numpy)
+            },
+            // numpy arrays are computed element-wise by the operators, lists with a comprehension
+            Instruction::Binary(_, operator, left, right) => {
+                let operator = operator.symbol();
+                let (left_str, right_str) = (self.operand(left), self.operand(right));
+                match (left.ty() == Type::Vector, right.ty() == Type::Vector) {
+                    (true, true) if !numpy => format!("[l_ {} r_ for l_, r_ in zip({}, {})]", operator, left_str, right_str),
+                    (true, false) if !numpy => format!("[e_ {} {} for e_ in {}]", operator, right_str, left_str),
+                    (false, true) if !numpy => format!("[{} {} e_ for e_ in {}]", left_str, operator, right_str),
+                    _ => {
+                        operation = true;
+                        format!("{} {} {}", left_str, operator, right_str)
+                    },
+                }
+            },
+            Instruction::Copy(_, value) => self.operand(value),
+            Instruction::Vector(_, elements) => {
+                let elements = elements.iter().map(|element| self.operand(element)).collect::<Vec<String>>();
+                if numpy {
+                    format!("np.array([{}])", elements.join(", "))
+                } else {
+                    format!("[{}]", elements.join(", "))
+                }
+            },
+            Instruction::Index(_, vector, index) => format!("{}[{}]", self.operand(vector), self.position(index)),
+            Instruction::Slice(_, vector, start, end) => {
+                format!("{}[{}:{}]", self.operand(vector), self.position(start), self.position(end))
+            },
+            // records are dicts keyed by field name
+            Instruction::Field(_, record, field) => format!("{}[{}]", self.operand(record), string_literal(field)),
+        };
+
+        match instruction.targets().as_slice() {
+            // functions returning several values return them as a tuple
+            targets @ [_, _, ..] => {
+                let names = targets.iter().map(|target| self.operand_name(target)).collect::<Vec<String>>();
+                Some(format!("{} = {}", names.join(", "), value))
+            },
+            [target] if self.inliner.inlines(target) => {
+                self.inliner.keep(target, if operation { format!("({})", value) } else { value });
+                None
+            },
+            [target] => Some(format!("{} = {}", self.operand_name(target), value)),
+            [] => None,
+        }
+    }
+
+    // Code of an output section - a text file receives what would have been displayed
+    fn output_section(&mut self, section: &OutputSection) -> String {
+        match &section.file {
+            Some(file) => {
+                let body = section.operations.iter().map(|operation| self.output(operation, Some("output_file"))).collect::<Vec<String>>();
+                format!("with open({}, \"w\") as output_file:\n    {}", string_literal(file), body.join("\n").replace('\n', "\n    "))
+            },
+            None => section.operations.iter().map(|operation| self.output(operation, None)).collect::<Vec<String>>().join("\n"),
+        }
+    }
+
+    // Converts one operation of the output section into the Python code displaying it, in the file when there is one
+    fn output(&mut self, operation: &OutputOp, file: Option<&str>) -> String {
+        match operation {
+            // every piece is displayed on the same line
+            OutputOp::Line(pieces) => {
+                let mut args = pieces.iter().map(|piece| match piece {
+                    Piece::Text(text) => string_literal(text),
+                    Piece::Value(value, precision) => self.formatted(value, *precision),
+                }).collect::<Vec<String>>();
+                if args.len() > 1 {
+                    args.push("sep=\"\"".to_string());
+                }
+                if let Some(file) = file {
+                    args.push(format!("file={}", file));
+                }
+                format!("print({})", args.join(", "))
+            },
+            // Saves named values to a CSV (header and one row) or JSON (one object) file
+            OutputOp::Write(file, values) => {
+                let writer = if file_format(file) == FileFormat::Json { "write_json" } else { "write_csv" };
+                let names = values.iter().map(|(name, _)| string_literal(name)).collect::<Vec<String>>();
+                let values = values.iter().map(|(_, value)| self.operand(value)).collect::<Vec<String>>();
+                format!("{}({}, [{}], [{}])", writer, string_literal(file), names.join(", "), values.join(", "))
+            },
+        }
+    }
+
+    // Displays a number with exactly the requested number of decimals
+    fn formatted(&mut self, value: &Operand, precision: Option<u32>) -> String {
+        match precision {
+            Some(decimals) => format!("format({}, \".{}f\")", self.operand(value), decimals),
+            None => self.operand(value),
+        }
+    }
+
+    // Positions in a vector must be integers, DA numbers are converted unless they are integer constants
+    fn position(&mut self, operand: &Operand) -> String {
+        match operand {
+            Operand::Constant(value) if value.fract() == 0.0 => self.operand(operand),
+            _ => format!("int({})", self.operand(operand)),
+        }
+    }
+
+    // Code for the value of an operand
+    fn operand(&mut self, operand: &Operand) -> String {
+        self.inliner.take(operand).unwrap_or_else(|| self.operand_name(operand))
+    }
+
+    // Name assigned for an operand
+    fn operand_name(&self, operand: &Operand) -> String {
+        names::PYTHON.operand(operand)
+    }
+}
+
+fn column_to_python(column: &Column) -> String {
+    match column {
+        Column::Index(n) => n.to_string(),
+        Column::Name(name) => string_literal(name),
+    }
+}
+
+// String literal escaping quotes, backslashes and control characters; other characters are written as UTF-8,
+// the encoding of Python source files
+fn string_literal(text: &str) -> String {
+    backend::quoted(text, '"', |code| format!("\\x{:02x}", code))
+}
+
+// Convert row filters to the body of their Python predicate, 'col' being its parameter
+fn filter_to_python(expr: &Expression) -> String {
+    match expr {
+        Expression::Comparison(left, comparator, right) => {
+            format!("{} {} {}", filter_to_python(left), comparator.symbol(), filter_to_python(right))
+        },
+        Expression::FunctionCall(_, args) => {
+            let args_str = args.iter().map(filter_to_python).collect::<Vec<String>>();
+            format!("col({})", args_str.join(", "))
+        },
+        Expression::BinaryOp(left, operator, right) => {
+            format!("({} {} {})", filter_to_python(left), operator.symbol(), filter_to_python(right))
+        },
+        Expression::Literal(LiteralValue::Num(n)) => n.to_string(),
+        _ => unreachable!("'where' filters only contain col(NUM), numbers and arithmetic"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer, parser, semantic, ir, optimize};
+    use crate::types::TypeEnv;
+
+    // Python code of the process section of a program reading x and displaying v
+    fn process_to_python(declarations: &str, process: &str) -> String {
+        let source = format!("data: x : vector, v : vector, {} input: x = read(\"a.csv\", true, 0) process: {} output: v end.", declarations, process);
+        let nodes = parser::Parser::new(lexer::lexical_analysis(&source).unwrap()).parse().unwrap();
+        let types = TypeEnv::from_symbols(&semantic::analyze(&nodes).0);
+        let mut program = ir::lower(&nodes, &types);
+        optimize::optimize(&mut program).unwrap();
+        convert_to_python(&program, Library::Stdlib)
+    }
+
+    #[test]
+    fn slices_use_integer_bounds() {
+        assert!(process_to_python("n : number", "n = 2, v = x[1:10]").contains("v = x[1:10]"));
+        let code = process_to_python("n : number", "n = mean(x), v = x[0:n]");
+        assert!(code.contains("v = x[0:int(n)]"), "{}", code);
+    }
+
+    #[test]
+    fn reserved_names_are_prefixed() {
+        let code = process_to_python("sum : number, lambda : vector", "sum = mean(x), lambda = x * sum, v = lambda[0:2]");
+        assert!(code.contains("da_sum = "), "{}", code);
+        assert!(code.contains("da_lambda = "), "{}", code);
+        assert!(code.contains("v = da_lambda[0:2]"), "{}", code);
+    }
+}
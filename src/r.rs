@@ -0,0 +1,283 @@
+/*
+    This module translates our IR (Intermediate Representation) into an R script: the CSV files are loaded with
+    read.csv, the statistics computed with mean, sd, cor and lm, and the output section written with cat
+*/
+
+use crate::parser::{Expression, LiteralValue, FileFormat, file_format};
+use crate::ir::{Program, Instruction, Operand, Column, OutputSection, OutputOp, Piece, Inliner, number_to_string};
+use crate::types::Type;
+use crate::names;
+use crate::backend::{self, Backend};
+
+// Runtime defining the output file writers, the rest is base R
+const PRELUDE: &str = include_str!("runtime/prelude.R");
+
+#[derive(Default)]
+pub struct RBackend;
+
+impl Backend for RBackend {
+    fn name(&self) -> &'static str {
+        "r"
+    }
+
+    fn file_extension(&self) -> &'static str {
+        "R"
+    }
+
+    fn configure(&mut self, option: &str, _value: &str) -> Result<(), String> {
+        match option {
+            "dialect" => Err("The r target has no dialects".to_string()),
+            _ => Err(format!("The r target has no option '{}'", option)),
+        }
+    }
+
+    // The runtime is always included and the messages of the compiler go to the standard error,
+    // so the script written to the standard output runs as is even without --standalone, which only adds the shebang line
+    fn generate(&self, program: &Program, standalone: bool, _name: &str) -> String {
+        if standalone {
+            standalone_program(program)
+        } else {
+            script(program)
+        }
+    }
+
+    fn diagnostics(&self, program: &Program) -> Vec<String> {
+        names::R.renamed(program, "R")
+    }
+}
+
+// A complete script started with a shebang line, so it can be made executable
+pub fn standalone_program(program: &Program) -> String {
+    format!("#!/usr/bin/env Rscript\n{}", script(program))
+}
+
+// The runtime, then the code of the program
+pub fn script(program: &Program) -> String {
+    format!("{}\n# ---- program ----\n\n{}", PRELUDE, convert_to_r(program))
+}
+
+// convert a program into its R representation
+pub fn convert_to_r(program: &Program) -> String {
+    let mut converter = RConverter { inliner: Inliner::new(program) };
+    let mut lines = program.instructions.iter()
+        .filter_map(|instruction| converter.instruction(instruction))
+        .collect::<Vec<String>>();
+    lines.extend(program.outputs.iter().map(|section| converter.output_section(section)));
+    lines.join("\n")
+}
+
+// Temporaries chosen by the Inliner are written inside the expression using them, the others are assigned
+struct RConverter {
+    inliner: Inliner,
+}
+
+impl RConverter {
+    // Assignment of the values computed by an instruction (None when the value is inlined where it is used)
+    fn instruction(&mut self, instruction: &Instruction) -> Option<String> {
+        // operators must be put in parentheses when their value is inlined into another expression
+        let mut operation = false;
+        let value = match instruction {
+            Instruction::Read(_, file, header, column, filter) => {
+                let table = read_csv(file, *header);
+                match filter {
+                    // the filter needs the other columns of the file, so the table is kept in a local variable
+                    Some(filter) => format!("local({{ table <- {}; table[[{}]][{}] }})", table, column_to_r(column), filter_to_r(filter, "table")),
+                    None => format!("{}[[{}]]", table, column_to_r(column)),
+                }
+            },
+            // a file read several times is loaded once as a table, its columns are then taken from the table
+            Instruction::LoadTable(_, file, header) => read_csv(file, *header),
+            Instruction::Extract(_, table, column, filter) => {
+                let table = self.operand(table);
+                match filter {
+                    Some(filter) => format!("{}[[{}]][{}]", table, column_to_r(column), filter_to_r(filter, &table)),
+                    None => format!("{}[[{}]]", table, column_to_r(column)),
+                }
+            },
+            Instruction::Call(_, function, args) => {
+                let args = args.iter().map(|arg| self.operand(arg)).collect::<Vec<String>>();
+                function.r_call(&args)
+            },
+            // R arithmetic is element-wise on vectors
+            Instruction::Binary(_, operator, left, right) => {
+                operation = true;
+                format!("{} {} {}", self.operand(left), operator.symbol(), self.operand(right))
+            },
+            Instruction::Copy(_, value) => self.operand(value),
+            Instruction::Vector(_, elements) => {
+                let elements = elements.iter().map(|element| self.operand(element)).collect::<Vec<String>>();
+                format!("c({})", elements.join(", "))
+            },
+            // DA positions start at 0 and R positions at 1
+            Instruction::Index(_, vector, index) => format!("{}[{}]", self.operand(vector), self.position(index, 1.0)),
+            // the end of a DA slice is excluded, the end of an R range is included
+            // x[a:b] of R gives NA past the end of the vector, slice of the runtime stops at its end
+            Instruction::Slice(_, vector, start, end) => {
+                format!("slice({}, {}, {})", self.operand(vector), self.operand(start), self.operand(end))
+            },
+            // records are named lists
+            Instruction::Field(_, record, field) => format!("{}[[{}]]", self.operand(record), string_literal(field)),
+        };
+
+        match instruction.targets().as_slice() {
+            // functions returning several values return them as a vector, assigned element by element
+            targets @ [_, _, ..] => {
+                let mut lines = vec![format!(".values <- {}", value)];
+                for (position, target) in targets.iter().enumerate() {
+                    lines.push(format!("{} <- .values[[{}]]", self.operand_name(target), position + 1));
+                }
+                Some(lines.join("\n"))
+            },
+            [target] if self.inliner.inlines(target) => {
+                self.inliner.keep(target, if operation { format!("({})", value) } else { value });
+                None
+            },
+            [target] => Some(format!("{} <- {}", self.operand_name(target), value)),
+            [] => None,
+        }
+    }
+
+    // Code of an output section - a text file receives what would have been displayed, between sink calls
+    fn output_section(&mut self, section: &OutputSection) -> String {
+        let mut lines = section.operations.iter().map(|operation| self.output(operation)).collect::<Vec<String>>();
+        if let Some(file) = &section.file {
+            lines.insert(0, format!("sink({})", string_literal(file)));
+            lines.push("sink()".to_string());
+        }
+        lines.join("\n")
+    }
+
+    // Converts one operation of the output section into the cat call displaying it
+    fn output(&mut self, operation: &OutputOp) -> String {
+        match operation {
+            // every piece is displayed on the same line, the newline is added to the last text when there is one
+            OutputOp::Line(pieces) => {
+                let mut args = Vec::new();
+                for (position, piece) in pieces.iter().enumerate() {
+                    match piece {
+                        Piece::Text(text) if position + 1 == pieces.len() => args.push(string_literal(&format!("{}\n", text))),
+                        Piece::Text(text) => args.push(string_literal(text)),
+                        Piece::Value(value, precision) => args.push(self.displayed(value, *precision)),
+                    }
+                }
+                if !matches!(pieces.last(), Some(Piece::Text(_))) {
+                    args.push(string_literal("\n"));
+                }
+                if args.len() > 1 {
+                    args.push("sep = \"\"".to_string());
+                }
+                format!("cat({})", args.join(", "))
+            },
+            // Saves named values to a CSV (header and one row) or JSON (one object) file
+            OutputOp::Write(file, values) => {
+                let writer = if file_format(file) == FileFormat::Json { "write_json" } else { "write_csv" };
+                let names = values.iter().map(|(name, _)| string_literal(name)).collect::<Vec<String>>();
+                let values = values.iter().map(|(_, value)| self.operand(value)).collect::<Vec<String>>();
+                format!("{}({}, c({}), list({}))", writer, string_literal(file), names.join(", "), values.join(", "))
+            },
+        }
+    }
+
+    // Code displaying a value with cat: numbers with exactly the requested decimals, vectors separated by spaces
+    // and records as field=value pairs
+    fn displayed(&mut self, value: &Operand, precision: Option<u32>) -> String {
+        let mut code = self.operand(value);
+        if let Some(decimals) = precision {
+            code = format!("formatC({}, format = \"f\", digits = {})", code, decimals);
+        }
+        match value.ty() {
+            Type::Vector => format!("paste({}, collapse = \" \")", code),
+            Type::Record => format!("paste(names({0}), unlist({0}), sep = \"=\", collapse = \" \")", code),
+            _ => code,
+        }
+    }
+
+    // Position in R of a DA position, shifted by `offset`
+    fn position(&mut self, operand: &Operand, offset: f64) -> String {
+        match operand {
+            Operand::Constant(value) => number_to_string(value + offset),
+            _ if offset == 0.0 => self.operand(operand),
+            _ => format!("{} + {}", self.operand(operand), number_to_string(offset)),
+        }
+    }
+
+    // Code for the value of an operand
+    fn operand(&mut self, operand: &Operand) -> String {
+        self.inliner.take(operand).unwrap_or_else(|| self.operand_name(operand))
+    }
+
+    // Name assigned for an operand
+    fn operand_name(&self, operand: &Operand) -> String {
+        names::R.operand(operand)
+    }
+}
+
+// Loads a whole file, removing the spaces around the fields; the column names of the header are kept as they are
+// (read.csv would otherwise turn "unit price" into "unit.price"), so columns read by name are found
+fn read_csv(file: &str, header: bool) -> String {
+    format!("read.csv({}, header = {}, strip.white = TRUE, check.names = FALSE)", string_literal(file), if header { "TRUE" } else { "FALSE" })
+}
+
+// Columns are numbered from 1 in R
+fn column_to_r(column: &Column) -> String {
+    match column {
+        Column::Index(n) => (n + 1).to_string(),
+        Column::Name(name) => string_literal(name),
+    }
+}
+
+// String literal escaping quotes, backslashes and control characters; other characters are written as UTF-8
+fn string_literal(text: &str) -> String {
+    backend::quoted(text, '"', |code| format!("\\x{:02x}", code))
+}
+
+// Convert row filters to a logical vector over the columns of `table`, col(N) being the column N + 1
+fn filter_to_r(expr: &Expression, table: &str) -> String {
+    match expr {
+        Expression::Comparison(left, comparator, right) => {
+            format!("{} {} {}", filter_to_r(left, table), comparator.symbol(), filter_to_r(right, table))
+        },
+        Expression::FunctionCall(_, args) => match args.as_slice() {
+            [Expression::Literal(LiteralValue::Num(n))] => format!("{}[[{}]]", table, n + 1),
+            _ => unreachable!("col() is checked to take a column number by the type checker"),
+        },
+        Expression::BinaryOp(left, operator, right) => {
+            format!("({} {} {})", filter_to_r(left, table), operator.symbol(), filter_to_r(right, table))
+        },
+        Expression::Literal(LiteralValue::Num(n)) => n.to_string(),
+        _ => unreachable!("'where' filters only contain col(NUM), numbers and arithmetic"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer, parser, semantic, ir, optimize};
+    use crate::types::TypeEnv;
+
+    // R code of the process section of a program reading x and displaying v
+    fn process_to_r(declarations: &str, process: &str) -> String {
+        let source = format!("data: x : vector, v : vector, {} input: x = read(\"a.csv\", true, 0) process: {} output: v end.", declarations, process);
+        let nodes = parser::Parser::new(lexer::lexical_analysis(&source).unwrap()).parse().unwrap();
+        let types = TypeEnv::from_symbols(&semantic::analyze(&nodes).0);
+        let mut program = ir::lower(&nodes, &types);
+        optimize::optimize(&mut program).unwrap();
+        convert_to_r(&program)
+    }
+
+    #[test]
+    fn slices_stop_at_the_end_of_the_vector() {
+        assert!(process_to_r("n : number", "n = 2, v = x[1:10]").contains("v <- slice(x, 1, 10)"));
+        let code = process_to_r("n : number", "n = mean(x), v = (x * 2)[n:n + 3]");
+        assert!(code.contains("v <- slice((x * 2), n, (n + 3))"), "{}", code);
+        assert!(PRELUDE.contains("slice <- function(xs, start, end) {\n  xs[seq_len(max(0, min(end, length(xs)) - start)) + start]\n}"));
+    }
+
+    #[test]
+    fn reserved_names_are_prefixed() {
+        let code = process_to_r("table : vector, slice : number", "table = x * 2, slice = mean(table), v = table - slice");
+        assert!(code.contains("da_table <- x * 2"), "{}", code);
+        assert!(code.contains("da_slice <- mean(da_table)"), "{}", code);
+        assert!(code.contains("v <- da_table - da_slice"), "{}", code);
+    }
+}
@@ -0,0 +1,300 @@
+% Runtime of the programs generated from DA: CSV loading, the builtin functions and the output files.
+% Only ISO predicates and the usual list predicates are used, the header of each dialect adds what differs.
+
+% ---- CSV files ----
+
+% A table holds the column names (none without a header) and the rows, each row a list of atoms
+load_csv(File, Header, table(Names, Rows)) :-
+    open(File, read, Stream),
+    csv_read_lines(Stream, Lines),
+    close(Stream),
+    csv_rows(Lines, AllRows),
+    csv_header(Header, AllRows, Names, Rows).
+
+csv_header(true, [Names|Rows], Names, Rows) :- !.
+csv_header(_, Rows, none, Rows).
+
+% Values of a column, keeping the rows for which the filter holds once col(N) is replaced by the numbers of the row
+csv_column_where(Table, Column, Filter, Values) :-
+    Table = table(_, Rows),
+    csv_index(Table, Column, Index),
+    csv_select(Rows, Index, Filter, Values).
+
+csv_column(Table, Column, Values) :-
+    csv_column_where(Table, Column, true, Values).
+
+load_data_column(File, Header, Column, Values) :-
+    load_csv(File, Header, Table),
+    csv_column(Table, Column, Values).
+
+load_data_column_where(File, Header, Column, Filter, Values) :-
+    load_csv(File, Header, Table),
+    csv_column_where(Table, Column, Filter, Values).
+
+csv_select([], _, _, []).
+csv_select([Row|Rows], Index, Filter, Values) :-
+    (   csv_keep(Filter, Row)
+    ->  nth0(Index, Row, Field),
+        csv_number(Field, Value),
+        Values = [Value|Rest]
+    ;   Values = Rest
+    ),
+    csv_select(Rows, Index, Filter, Rest).
+
+csv_keep(true, _) :- !.
+csv_keep(Filter, Row) :-
+    csv_substitute(Filter, Row, Goal),
+    call(Goal).
+
+csv_substitute(col(N), Row, Value) :- !,
+    nth0(N, Row, Field),
+    csv_number(Field, Value).
+csv_substitute(Term, Row, Result) :-
+    compound(Term), !,
+    Term =.. [Name|Args],
+    csv_substitute_all(Args, Row, Results),
+    Result =.. [Name|Results].
+csv_substitute(Term, _, Term).
+
+csv_substitute_all([], _, []).
+csv_substitute_all([Arg|Args], Row, [Result|Results]) :-
+    csv_substitute(Arg, Row, Result),
+    csv_substitute_all(Args, Row, Results).
+
+% Position of a column given by its position or by its name in the header
+csv_index(_, Column, Column) :-
+    integer(Column), !.
+csv_index(table(Names, _), Column, Index) :-
+    text_atom(Column, Name),
+    Names \== none,
+    nth0(Index, Names, Name), !.
+csv_index(_, Column, _) :-
+    throw(error(existence_error(column, Column), load_csv/3)).
+
+csv_number(Field, Value) :-
+    atom_chars(Field, Chars),
+    catch(number_chars(Value, Chars), _, fail), !.
+csv_number(Field, _) :-
+    throw(error(type_error(number, Field), load_csv/3)).
+
+csv_read_lines(Stream, Lines) :-
+    get_char(Stream, Char),
+    csv_read_line(Char, Stream, Line, End),
+    (   End == end_of_file
+    ->  ( Line == [] -> Lines = [] ; Lines = [Line] )
+    ;   Lines = [Line|Rest],
+        csv_read_lines(Stream, Rest)
+    ).
+
+csv_read_line(end_of_file, _, [], end_of_file) :- !.
+csv_read_line('\n', _, [], newline) :- !.
+csv_read_line(Char, Stream, [Char|Chars], End) :-
+    get_char(Stream, Next),
+    csv_read_line(Next, Stream, Chars, End).
+
+% Splits the lines at their commas, skipping the blank lines
+csv_rows([], []).
+csv_rows([Line|Lines], Rows) :-
+    csv_trim(Line, Trimmed),
+    (   Trimmed == []
+    ->  Rows = Rest
+    ;   csv_fields(Line, Fields),
+        Rows = [Fields|Rest]
+    ),
+    csv_rows(Lines, Rest).
+
+csv_fields(Chars, [Field|Fields]) :-
+    csv_split(Chars, FieldChars, Rest),
+    csv_trim(FieldChars, Trimmed),
+    atom_chars(Field, Trimmed),
+    (   Rest == none
+    ->  Fields = []
+    ;   csv_fields(Rest, Fields)
+    ).
+
+csv_split([], [], none).
+csv_split([','|Rest], [], Rest) :- !.
+csv_split([Char|Chars], [Char|Field], Rest) :-
+    csv_split(Chars, Field, Rest).
+
+% Removes the spaces and quotes around a field
+csv_trim(Chars, Trimmed) :-
+    csv_drop(Chars, Left),
+    reverse(Left, Reversed),
+    csv_drop(Reversed, Right),
+    reverse(Right, Trimmed).
+
+csv_drop([Char|Chars], Rest) :-
+    csv_blank(Char), !,
+    csv_drop(Chars, Rest).
+csv_drop(Chars, Chars).
+
+csv_blank(' ').
+csv_blank('\t').
+csv_blank('\r').
+csv_blank('"').
+
+% Text written as an atom, a string, a list of codes or a list of characters
+text_atom(Text, Atom) :-
+    (   atom(Text)
+    ->  Atom = Text
+    ;   Text = [Code|_], integer(Code)
+    ->  atom_codes(Atom, Text)
+    ;   atom_chars(Atom, Text)
+    ).
+
+% ---- builtin functions ----
+
+stats_sum([], 0).
+stats_sum([X|Xs], Sum) :-
+    stats_sum(Xs, Rest),
+    Sum is X + Rest.
+
+% sum of the products of the deviations of Xs and Ys from their means
+stats_deviations(Xs, Ys, Sum) :-
+    mean(Xs, MX),
+    mean(Ys, MY),
+    stats_products(Xs, Ys, MX, MY, Sum).
+
+stats_products([X|Xs], [Y|Ys], MX, MY, Sum) :- !,
+    stats_products(Xs, Ys, MX, MY, Rest),
+    Sum is (X - MX) * (Y - MY) + Rest.
+stats_products(_, _, _, _, 0).
+
+stats_min([X|Xs], Min) :- stats_min(Xs, X, Min).
+stats_min([], Min, Min).
+stats_min([X|Xs], Acc, Min) :-
+    ( X < Acc -> Next = X ; Next = Acc ),
+    stats_min(Xs, Next, Min).
+
+stats_max([X|Xs], Max) :- stats_max(Xs, X, Max).
+stats_max([], Max, Max).
+stats_max([X|Xs], Acc, Max) :-
+    ( X > Acc -> Next = X ; Next = Acc ),
+    stats_max(Xs, Next, Max).
+
+mean(Xs, Mean) :-
+    stats_sum(Xs, Sum),
+    length(Xs, N),
+    Mean is Sum * 1.0 / N.
+
+% sample standard deviation
+stddev(Xs, StdDev) :-
+    stats_deviations(Xs, Xs, Sum),
+    length(Xs, N),
+    StdDev is sqrt(Sum / (N - 1)).
+
+% Y = A + B X
+regressionb(Xs, Ys, B) :-
+    stats_deviations(Xs, Ys, Sxy),
+    stats_deviations(Xs, Xs, Sxx),
+    B is Sxy / Sxx.
+
+regressiona(Xs, Ys, A) :-
+    regressionb(Xs, Ys, B),
+    mean(Xs, MX),
+    mean(Ys, MY),
+    A is MY - B * MX.
+
+regression(Xs, Ys, [A, B]) :-
+    regressiona(Xs, Ys, A),
+    regressionb(Xs, Ys, B).
+
+correlation(Xs, Ys, R) :-
+    stats_deviations(Xs, Ys, Sxy),
+    stats_deviations(Xs, Xs, Sxx),
+    stats_deviations(Ys, Ys, Syy),
+    R is Sxy / sqrt(Sxx * Syy).
+
+describe(Xs, [count-N, mean-Mean, stddev-StdDev, min-Min, max-Max]) :-
+    length(Xs, N),
+    mean(Xs, Mean),
+    stddev(Xs, StdDev),
+    stats_min(Xs, Min),
+    stats_max(Xs, Max).
+
+% elements from Start to End (exclusive)
+slice(Xs, Start, End, Slice) :-
+    slice(Xs, 0, Start, End, Slice).
+
+slice([X|Xs], Index, Start, End, Slice) :-
+    Index < End, !,
+    (   Index >= Start
+    ->  Slice = [X|Rest]
+    ;   Slice = Rest
+    ),
+    Next is Index + 1,
+    slice(Xs, Next, Start, End, Rest).
+slice(_, _, _, _, []).
+
+% ---- output files ----
+
+output_items([], _, _, _).
+output_items([Item|Items], Stream, Writer, Separator) :-
+    call(Writer, Stream, Item),
+    ( Items == [] -> true ; write(Stream, Separator) ),
+    output_items(Items, Stream, Writer, Separator).
+
+output_text(Stream, Text) :-
+    text_atom(Text, Atom),
+    write(Stream, Atom).
+
+% A header with the names and one row with the values; vectors and records are written in a single quoted field
+write_csv(File, Names, Values) :-
+    open(File, write, Stream),
+    output_items(Names, Stream, output_text, ','),
+    nl(Stream),
+    output_items(Values, Stream, csv_value, ','),
+    nl(Stream),
+    close(Stream).
+
+csv_value(Stream, Value) :-
+    number(Value), !,
+    write(Stream, Value).
+csv_value(Stream, Values) :-
+    write(Stream, '"'),
+    output_items(Values, Stream, csv_element, ' '),
+    write(Stream, '"').
+
+csv_element(Stream, Field-Value) :- !,
+    write(Stream, Field),
+    write(Stream, '='),
+    write(Stream, Value).
+csv_element(Stream, Value) :-
+    write(Stream, Value).
+
+% One object with a member per name
+write_json(File, Names, Values) :-
+    open(File, write, Stream),
+    json_object(Stream, Names, Values),
+    nl(Stream),
+    close(Stream).
+
+json_object(Stream, Names, Values) :-
+    write(Stream, '{'),
+    json_members(Names, Values, Stream),
+    write(Stream, '}').
+
+json_members([], [], _).
+json_members([Name|Names], [Value|Values], Stream) :-
+    write(Stream, '"'),
+    output_text(Stream, Name),
+    write(Stream, '": '),
+    json_value(Stream, Value),
+    ( Names == [] -> true ; write(Stream, ', ') ),
+    json_members(Names, Values, Stream).
+
+json_value(Stream, Value) :-
+    number(Value), !,
+    write(Stream, Value).
+json_value(Stream, [Field-Value|Fields]) :- !,
+    json_pairs([Field-Value|Fields], Names, Values),
+    json_object(Stream, Names, Values).
+json_value(Stream, Values) :-
+    write(Stream, '['),
+    output_items(Values, Stream, json_value, ', '),
+    write(Stream, ']').
+
+json_pairs([], [], []).
+json_pairs([Name-Value|Pairs], [Name|Names], [Value|Values]) :-
+    json_pairs(Pairs, Names, Values).
@@ -0,0 +1,91 @@
+# Runtime of the programs generated from DA with the standard library only: CSV loading, the builtin functions
+# that have no statistics equivalent and the output files. Needs Python 3.10 (statistics.correlation and linear_regression).
+
+import csv
+import json
+import statistics
+from collections import namedtuple
+
+# ---- CSV files ----
+
+# The column names (None without a header) and the rows, each row a list of fields
+Table = namedtuple("Table", ["names", "rows"])
+
+
+def load_csv(path, header):
+    with open(path, newline="") as file:
+        rows = [[field.strip() for field in row] for row in csv.reader(file, skipinitialspace=True)]
+    rows = [row for row in rows if any(row)]
+    if header and rows:
+        return Table(rows[0], rows[1:])
+    return Table(None, rows)
+
+
+# Values of a column, keeping the rows accepted by keep ('col' gives keep the numbers of the current row)
+def csv_column(table, column, keep=lambda col: True):
+    index = csv_index(table, column)
+    return [csv_number(row[index]) for row in table.rows if keep(lambda n: csv_number(row[n]))]
+
+
+def read_csv(path, header, column, keep=lambda col: True):
+    return csv_column(load_csv(path, header), column, keep)
+
+
+# Position of a column given by its position or by its name in the header
+def csv_index(table, column):
+    if isinstance(column, str):
+        if table.names is None or column not in table.names:
+            raise KeyError(f"load_csv: no column named {column}")
+        return table.names.index(column)
+    return column
+
+
+def csv_number(field):
+    try:
+        return int(field)
+    except ValueError:
+        try:
+            return float(field)
+        except ValueError:
+            raise ValueError(f"load_csv: not a number {field!r}") from None
+
+
+# ---- builtin functions ----
+
+# y = a + b x
+def regression(xs, ys):
+    fit = statistics.linear_regression(xs, ys)
+    return fit.intercept, fit.slope
+
+
+def describe(xs):
+    return {
+        "count": len(xs),
+        "mean": statistics.fmean(xs),
+        "stddev": statistics.stdev(xs),
+        "min": min(xs),
+        "max": max(xs),
+    }
+
+
+# ---- output files ----
+
+# A header with the names and one row with the values; vectors and records are written in a single quoted field
+def write_csv(path, names, row):
+    def field(value):
+        if isinstance(value, dict):
+            return '"' + " ".join(f"{name}={item}" for name, item in value.items()) + '"'
+        if isinstance(value, list):
+            return '"' + " ".join(str(item) for item in value) + '"'
+        return str(value)
+
+    with open(path, "w") as file:
+        file.write(",".join(names) + "\n")
+        file.write(",".join(field(value) for value in row) + "\n")
+
+
+# One object with a member per name
+def write_json(path, names, row):
+    with open(path, "w") as file:
+        json.dump(dict(zip(names, row)), file)
+        file.write("\n")
@@ -0,0 +1,161 @@
+;; Runtime of the programs generated from DA: CSV loading, the builtin functions and the output files.
+;; Only R7RS-small procedures are used, the header of each dialect adapts the few that differ.
+
+;; ---- CSV files ----
+
+;; A table is a pair: the column names (#f without a header) and the rows, each row a list of fields
+(define (load-csv path header)
+  (call-with-input-file path
+    (lambda (port)
+      (let loop ((rows '()))
+        (let ((line (read-line port)))
+          (cond ((eof-object? line)
+                 (let ((rows (reverse rows)))
+                   (if (and header (pair? rows))
+                       (cons (car rows) (cdr rows))
+                       (cons #f rows))))
+                ((csv-blank? line) (loop rows))
+                (else (loop (cons (csv-split line) rows)))))))))
+
+;; Values of a column, keeping the rows accepted by keep? ('col' gives keep? the numbers of the current row)
+(define (csv-column-where table column keep?)
+  (let ((index (csv-index table column)))
+    (let loop ((rows (cdr table)) (result '()))
+      (cond ((null? rows) (reverse result))
+            ((keep? (lambda (n) (csv-number (list-ref (car rows) n))))
+             (loop (cdr rows) (cons (csv-number (list-ref (car rows) index)) result)))
+            (else (loop (cdr rows) result))))))
+
+(define (csv-column table column)
+  (csv-column-where table column (lambda (col) #t)))
+
+(define (read-csv path header column)
+  (csv-column (load-csv path header) column))
+
+(define (read-csv-where path header column keep?)
+  (csv-column-where (load-csv path header) column keep?))
+
+;; Position of a column given by its position or by its name in the header
+(define (csv-index table column)
+  (if (string? column)
+      (let loop ((names (or (car table) '())) (index 0))
+        (cond ((null? names) (error "load-csv: no column named" column))
+              ((string=? (car names) column) index)
+              (else (loop (cdr names) (+ index 1)))))
+      column))
+
+(define (csv-number field)
+  (or (string->number field) (error "load-csv: not a number" field)))
+
+;; Splits a line at its commas, trimming the spaces and quotes around each field
+(define (csv-split line)
+  (let loop ((chars (string->list line)) (field '()) (fields '()))
+    (cond ((null? chars) (reverse (cons (csv-trim (reverse field)) fields)))
+          ((char=? (car chars) #\,) (loop (cdr chars) '() (cons (csv-trim (reverse field)) fields)))
+          (else (loop (cdr chars) (cons (car chars) field) fields)))))
+
+(define (csv-trim chars)
+  (define (drop chars)
+    (if (and (pair? chars) (or (char-whitespace? (car chars)) (char=? (car chars) #\")))
+        (drop (cdr chars))
+        chars))
+  (list->string (reverse (drop (reverse (drop chars))))))
+
+(define (csv-blank? line)
+  (let loop ((chars (string->list line)))
+    (or (null? chars) (and (char-whitespace? (car chars)) (loop (cdr chars))))))
+
+;; ---- builtin functions ----
+
+(define (stats-sum xs) (apply + xs))
+
+;; sum of the products of the deviations of xs and ys from their means
+(define (stats-deviations xs ys)
+  (let ((mx (mean xs)) (my (mean ys)))
+    (stats-sum (map (lambda (x y) (* (- x mx) (- y my))) xs ys))))
+
+(define (mean xs)
+  (/ (* 1.0 (stats-sum xs)) (length xs)))
+
+;; sample standard deviation
+(define (stddev xs)
+  (sqrt (/ (stats-deviations xs xs) (- (length xs) 1))))
+
+;; y = a + b x
+(define (regressionb xs ys)
+  (/ (stats-deviations xs ys) (stats-deviations xs xs)))
+
+(define (regressiona xs ys)
+  (- (mean ys) (* (regressionb xs ys) (mean xs))))
+
+(define (regression xs ys)
+  (values (regressiona xs ys) (regressionb xs ys)))
+
+(define (correlation xs ys)
+  (/ (stats-deviations xs ys) (sqrt (* (stats-deviations xs xs) (stats-deviations ys ys)))))
+
+(define (describe xs)
+  (list (cons 'count (length xs))
+        (cons 'mean (mean xs))
+        (cons 'stddev (stddev xs))
+        (cons 'min (apply min xs))
+        (cons 'max (apply max xs))))
+
+;; elements from start to end (exclusive)
+(define (slice xs start end)
+  (let loop ((xs xs) (index 0) (result '()))
+    (if (or (null? xs) (>= index end))
+        (reverse result)
+        (loop (cdr xs) (+ index 1) (if (>= index start) (cons (car xs) result) result)))))
+
+;; ---- output ----
+
+;; x written with exactly `decimals` digits after the point, e.g. (output-fixed 2.5 2) is "2.50"
+(define (output-fixed x decimals)
+  (let* ((scale (expt 10 decimals))
+         (scaled (exact (round (* (abs x) scale))))
+         (digits (number->string (remainder scaled scale)))
+         (sign (if (and (negative? x) (positive? scaled)) "-" ""))
+         (whole (string-append sign (number->string (quotient scaled scale)))))
+    (if (zero? decimals)
+        whole
+        (string-append whole "." (make-string (- decimals (string-length digits)) #\0) digits))))
+
+;; ---- output files ----
+
+(define (output-join strings separator)
+  (if (null? strings)
+      ""
+      (let loop ((strings (cdr strings)) (result (car strings)))
+        (if (null? strings)
+            result
+            (loop (cdr strings) (string-append result separator (car strings)))))))
+
+(define (output-record? value)
+  (and (pair? value) (pair? (car value))))
+
+;; A header with the names and one row with the values; vectors and records are written in a single quoted field
+(define (write-csv path names row)
+  (define (field value)
+    (cond ((number? value) (number->string value))
+          ((output-record? value)
+           (string-append "\"" (output-join (map (lambda (entry) (string-append (symbol->string (car entry)) "=" (number->string (cdr entry)))) value) " ") "\""))
+          (else (string-append "\"" (output-join (map number->string value) " ") "\""))))
+  (with-output-to-file path
+    (lambda ()
+      (display (output-join names ","))
+      (newline)
+      (display (output-join (map field row) ","))
+      (newline))))
+
+;; One object with a member per name
+(define (write-json path names row)
+  (define (json value)
+    (cond ((number? value) (number->string value))
+          ((output-record? value)
+           (string-append "{" (output-join (map (lambda (entry) (string-append "\"" (symbol->string (car entry)) "\": " (json (cdr entry)))) value) ", ") "}"))
+          (else (string-append "[" (output-join (map json value) ", ") "]"))))
+  (with-output-to-file path
+    (lambda ()
+      (display (string-append "{" (output-join (map (lambda (name value) (string-append "\"" name "\": " (json value))) names row) ", ") "}"))
+      (newline))))
@@ -0,0 +1,70 @@
+# Runtime of the programs generated from DA with pandas and numpy: CSV loading, the builtin functions
+# that have no numpy equivalent and the output files. Vectors are numpy arrays, so arithmetic is element-wise.
+
+import json
+
+import numpy as np
+import pandas as pd
+
+# ---- CSV files ----
+
+# A table is a DataFrame, its columns named by the header or numbered without one
+def load_csv(path, header):
+    return pd.read_csv(path, header=0 if header else None, skipinitialspace=True, skip_blank_lines=True)
+
+
+# Values of a column, keeping the rows accepted by keep ('col' gives keep the columns of the table, so a filter
+# is computed on whole columns at once)
+def csv_column(table, column, keep=None):
+    values = table[column] if isinstance(column, str) else table.iloc[:, column]
+    if keep is not None:
+        values = values[keep(lambda n: pd.to_numeric(table.iloc[:, n]))]
+    return pd.to_numeric(values).to_numpy(dtype=float)
+
+
+def read_csv(path, header, column, keep=None):
+    return csv_column(load_csv(path, header), column, keep)
+
+
+# ---- builtin functions ----
+
+def describe(xs):
+    return {
+        "count": len(xs),
+        "mean": float(np.mean(xs)),
+        "stddev": float(np.std(xs, ddof=1)),
+        "min": float(np.min(xs)),
+        "max": float(np.max(xs)),
+    }
+
+
+# ---- output files ----
+
+def output_value(value):
+    if isinstance(value, np.ndarray):
+        return value.tolist()
+    if isinstance(value, np.generic):
+        return value.item()
+    return value
+
+
+# A header with the names and one row with the values; vectors and records are written in a single quoted field
+def write_csv(path, names, row):
+    def field(value):
+        value = output_value(value)
+        if isinstance(value, dict):
+            return '"' + " ".join(f"{name}={item}" for name, item in value.items()) + '"'
+        if isinstance(value, list):
+            return '"' + " ".join(str(item) for item in value) + '"'
+        return str(value)
+
+    with open(path, "w") as file:
+        file.write(",".join(names) + "\n")
+        file.write(",".join(field(value) for value in row) + "\n")
+
+
+# One object with a member per name
+def write_json(path, names, row):
+    with open(path, "w") as file:
+        json.dump({name: output_value(value) for name, value in zip(names, row)}, file)
+        file.write("\n")
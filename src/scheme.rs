@@ -1,102 +1,313 @@
-/* 
-    This module translates our AST (Abstract Syntax Tree) into its Scheme representation.
+/*
+    This module translates our IR (Intermediate Representation) into its Scheme representation.
     Chris Kendall
     15 October 2023
 */
 
-use crate::parser::{TreeNode, Declaration, Assignment, Expression, LiteralValue};
+use crate::parser::{Expression, LiteralValue, Comparator, FileFormat, file_format};
+use crate::ir::{Program, Instruction, Operand, Column, OutputSection, OutputOp, Piece, Inliner};
+use crate::types::Type;
+use crate::names;
+use crate::backend::{self, Backend};
 
-// convert a TreeNode into its Scheme representation
-pub fn convert_to_scheme(node: &TreeNode) -> String {
-    node.to_scheme()
-}
+// Runtime defining every procedure the generated code calls (CSV loading, builtins, output files)
+const PRELUDE: &str = include_str!("runtime/prelude.scm");
 
-// Interface to convert types to scheme
-trait ToScheme {
-    fn to_scheme(&self) -> String;
+// Scheme implementation a self-contained program is written for
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Dialect {
+    #[default]
+    R7rs,
+    Racket,
+    Guile,
+    Chez,
 }
 
-// Implementing the ToScheme trait for TreeNode type
-impl ToScheme for TreeNode {
-    fn to_scheme(&self) -> String {
-        // match the type of TreeNode and return its Scheme representation
+impl Dialect {
+    pub const NAMES: [&'static str; 4] = ["r7rs", "racket", "guile", "chez"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "r7rs" => Some(Dialect::R7rs),
+            "racket" => Some(Dialect::Racket),
+            "guile" => Some(Dialect::Guile),
+            "chez" => Some(Dialect::Chez),
+            _ => None,
+        }
+    }
+
+    // Code placed before the prelude: the libraries it needs and the procedures that differ from R7RS
+    fn header(self) -> &'static str {
         match self {
-            // data declarations don't have a direct Scheme equivalent, so return an empty string
-            TreeNode::Data(_) => String::new(),
+            Dialect::R7rs => "(import (scheme base) (scheme char) (scheme file) (scheme inexact) (scheme write))",
+            // output files must be replaced when they already exist, and exact is defined from inexact->exact
+            Dialect::Racket => "#lang racket/base\n\
+                (require (only-in racket/base [with-output-to-file racket-with-output-to-file]))\n\
+                (define (with-output-to-file path thunk) (racket-with-output-to-file path thunk #:exists 'replace))\n\
+                (define (exact x) (inexact->exact x))",
+            Dialect::Guile => "(use-modules (ice-9 rdelim))",
+            // Chez has get-line instead of read-line, and define-values is built from call-with-values
+            Dialect::Chez => "(define (read-line port) (get-line port))\n\
+                (define chez-with-output-to-file with-output-to-file)\n\
+                (define (with-output-to-file path thunk) (chez-with-output-to-file path thunk 'replace))\n\
+                (define-syntax define-values\n  \
+                  (syntax-rules ()\n    \
+                    ((_ (name ...) expr)\n     \
+                     (begin (define name #f) ...\n            \
+                            (call-with-values (lambda () expr) (lambda results (set-values! (name ...) results)))))))\n\
+                (define-syntax set-values!\n  \
+                  (syntax-rules ()\n    \
+                    ((_ () results) #t)\n    \
+                    ((_ (name rest ...) results) (begin (set! name (car results)) (set-values! (rest ...) (cdr results))))))",
+        }
+    }
+}
 
-            // For input nodes, convert each assignment to Scheme and join them with newlines
-            TreeNode::Input(assignments) => {
-                assignments.iter().map(|a| a.to_scheme()).collect::<Vec<String>>().join("\n")
+// Scheme code for the R7RS dialect unless --dialect chooses another one
+#[derive(Default)]
+pub struct SchemeBackend {
+    dialect: Dialect,
+}
+
+impl Backend for SchemeBackend {
+    fn name(&self) -> &'static str {
+        "scheme"
+    }
+
+    fn file_extension(&self) -> &'static str {
+        "scm"
+    }
+
+    fn configure(&mut self, option: &str, value: &str) -> Result<(), String> {
+        match option {
+            "dialect" => {
+                self.dialect = Dialect::from_name(value)
+                    .ok_or_else(|| format!("Unknown Scheme dialect '{}', expected one of: {}", value, Dialect::NAMES.join(", ")))?;
             },
-            TreeNode::Process(assignments) => assignments.iter().map(|a| a.to_scheme()).collect::<Vec<String>>().join("\n"),
-            TreeNode::Output(exprs) => {
-                exprs.iter().map(|e| {
-                    match e {
-                        Expression::Literal(LiteralValue::Str(s)) => format!("(display \"{}\")\n(newline)", s),
-                        _ => format!("(display {})\n(newline)", e.to_scheme())
-                    }
-                }).collect::<Vec<String>>().join("\n")
-            },            
-            TreeNode::End => "".to_string(),
+            _ => return Err(format!("The scheme target has no option '{}'", option)),
+        }
+        Ok(())
+    }
+
+    fn generate(&self, program: &Program, standalone: bool, _name: &str) -> String {
+        if standalone {
+            standalone_program(program, self.dialect)
+        } else {
+            convert_to_scheme(program)
         }
     }
-}
 
-// Implementing the ToScheme trait for Declaration type
-impl ToScheme for Declaration {
-    fn to_scheme(&self) -> String {
-        // declarations don't have a direct Scheme equivalent
-        String::new()
+    fn diagnostics(&self, program: &Program) -> Vec<String> {
+        names::SCHEME.renamed(program, "Scheme")
     }
 }
 
-// Define how Assignments are converted to Scheme
-impl Assignment {
-    fn to_scheme(&self) -> String {
-        match self {
-            // If the assignment is a function call, convert it to its Scheme equivalent.
-            // For the "read" function, special handling is applied (convert "read" function to "read-csv" function)
-            Assignment::Assign(name, expr) => {
-                match expr {
-                    Expression::FunctionCall(func_name, args) => {
-                        let args_str: Vec<String> = args.iter().map(|arg| arg.to_scheme()).collect();
-                        match func_name.as_str() {
-                            "read" => {
-                                if let [file, false_val, col] = args_str.as_slice() {
-                                    return format!("(define {} (read-csv \"./{}\" {} {}))", name, file.trim_matches('"'), false_val, col);
-                                }
-                                String::new()
-                            },
-                            _ => format!("(define {} ({} {}))", name, func_name.to_lowercase(), args_str.join(" "))
-                        }
-                    },
-                    _ => expr.to_scheme()
+// A complete program for a dialect: its header, the runtime prelude, then the code of the program
+pub fn standalone_program(program: &Program, dialect: Dialect) -> String {
+    format!("{}\n\n{}\n;; ---- program ----\n\n{}", dialect.header(), PRELUDE, convert_to_scheme(program))
+}
+
+// convert a program into its Scheme representation
+pub fn convert_to_scheme(program: &Program) -> String {
+    let mut converter = SchemeConverter { inliner: Inliner::new(program) };
+    let mut lines = program.instructions.iter()
+        .filter_map(|instruction| converter.instruction(instruction))
+        .collect::<Vec<String>>();
+    lines.extend(program.outputs.iter().map(|section| converter.output_section(section)));
+    lines.join("\n")
+}
+
+// Temporaries chosen by the Inliner are written inside the expression using them, the others are defined
+struct SchemeConverter {
+    inliner: Inliner,
+}
+
+impl SchemeConverter {
+    // Definition of the values computed by an instruction (None when the value is inlined where it is used)
+    fn instruction(&mut self, instruction: &Instruction) -> Option<String> {
+        let value = match instruction {
+            Instruction::Read(_, file, header, column, filter) => {
+                let (file, column) = (string_literal(&format!("./{}", file)), column_to_scheme(column));
+                let header = if *header { "#t" } else { "#f" };
+                match filter {
+                    // a 'where' clause becomes a row predicate; 'col' is bound to a column accessor for the current row
+                    Some(filter) => format!("(read-csv-where {} {} {} (lambda (col) {}))", file, header, column, filter_to_scheme(filter)),
+                    None => format!("(read-csv {} {} {})", file, header, column),
                 }
-            }
+            },
+            // a file read several times is loaded once as a table, its columns are then taken from the table
+            Instruction::LoadTable(_, file, header) => {
+                format!("(load-csv {} {})", string_literal(&format!("./{}", file)), if *header { "#t" } else { "#f" })
+            },
+            Instruction::Extract(_, table, column, filter) => match filter {
+                Some(filter) => format!("(csv-column-where {} {} (lambda (col) {}))", self.operand(table), column_to_scheme(column), filter_to_scheme(filter)),
+                None => format!("(csv-column {} {})", self.operand(table), column_to_scheme(column)),
+            },
+            Instruction::Call(_, function, args) => {
+                let args = args.iter().map(|arg| self.operand(arg)).collect::<Vec<String>>();
+                function.scheme_call(&args)
+            },
+            // Arithmetic is mapped over the elements when one of the operands is a vector
+            Instruction::Binary(_, operator, left, right) => {
+                let operator = operator.symbol();
+                let (left_str, right_str) = (self.operand(left), self.operand(right));
+                match (left.ty() == Type::Vector, right.ty() == Type::Vector) {
+                    (true, true) => format!("(map {} {} {})", operator, left_str, right_str),
+                    (true, false) => format!("(map (lambda (e_) ({} e_ {})) {})", operator, right_str, left_str),
+                    (false, true) => format!("(map (lambda (e_) ({} {} e_)) {})", operator, left_str, right_str),
+                    (false, false) => format!("({} {} {})", operator, left_str, right_str),
+                }
+            },
+            Instruction::Copy(_, value) => self.operand(value),
+            // Vectors are Scheme lists
+            Instruction::Vector(_, elements) => {
+                let elements = elements.iter().map(|element| self.operand(element)).collect::<Vec<String>>();
+                format!("(list {})", elements.join(" "))
+            },
+            Instruction::Index(_, vector, index) => format!("(list-ref {} {})", self.operand(vector), self.operand(index)),
+            Instruction::Slice(_, vector, start, end) => {
+                format!("(slice {} {} {})", self.operand(vector), self.operand(start), self.operand(end))
+            },
+            // records are association lists keyed by field name
+            Instruction::Field(_, record, field) => format!("(cdr (assq '{} {}))", field, self.operand(record)),
+        };
+
+        match instruction.targets().as_slice() {
+            // functions returning several values return them as Scheme multiple values
+            targets @ [_, _, ..] => {
+                let names = targets.iter().map(|target| self.operand_name(target)).collect::<Vec<String>>();
+                Some(format!("(define-values ({}) {})", names.join(" "), value))
+            },
+            [target] if self.inliner.inlines(target) => {
+                self.inliner.keep(target, value);
+                None
+            },
+            [target] => Some(format!("(define {} {})", self.operand_name(target), value)),
+            [] => None,
         }
     }
-}
 
-// Define how Expressions are converted to Scheme
-impl Expression {
-    fn to_scheme(&self) -> String {
-        match self {
-            // Convert literals (strings, numbers, booleans) to their Scheme representation
-            Expression::Literal(lit) => match lit {
-                LiteralValue::Str(s) => format!("\"{}\"", s),  
-                LiteralValue::Num(n) => n.to_string(),
-                LiteralValue::Bool(b) => if *b { "#t" } else { "#f" }.to_string(),
+    // Code of an output section - a text file receives what would have been displayed
+    fn output_section(&mut self, section: &OutputSection) -> String {
+        let body = section.operations.iter().map(|operation| self.output(operation)).collect::<Vec<String>>().join("\n");
+        match &section.file {
+            Some(file) => format!("(with-output-to-file {}\n  (lambda ()\n    {}))", string_literal(file), body.replace('\n', "\n    ")),
+            None => body,
+        }
+    }
+
+    // Converts one operation of the output section into the Scheme code displaying it
+    fn output(&mut self, operation: &OutputOp) -> String {
+        match operation {
+            // every piece is displayed on the same line
+            OutputOp::Line(pieces) => {
+                let mut displays = pieces.iter().map(|piece| match piece {
+                    Piece::Text(text) => format!("(display {})", string_literal(text)),
+                    Piece::Value(value, precision) => format!("(display {})", self.formatted(value, *precision)),
+                }).collect::<Vec<String>>();
+                displays.push("(newline)".to_string());
+                displays.join("\n")
+            },
+            // Saves named values to a CSV (header and one row) or JSON (one object) file
+            OutputOp::Write(file, values) => {
+                let writer = if file_format(file) == FileFormat::Json { "write-json" } else { "write-csv" };
+                let names = values.iter().map(|(name, _)| string_literal(name)).collect::<Vec<String>>();
+                let values = values.iter().map(|(_, value)| self.operand(value)).collect::<Vec<String>>();
+                format!("({} {} '({}) (list {}))", writer, string_literal(file), names.join(" "), values.join(" "))
             },
-            // Convert identifiers directly to their name
-            Expression::Identifier(id) => id.to_string(),
-            // Convert function calls to their Scheme representation
-            Expression::FunctionCall(name, args) => {
-                let args_str = args.iter()
-                    .map(|arg| arg.to_scheme())
-                    .collect::<Vec<String>>()
-                    .join(" ");
-                format!("({} {})", name.to_lowercase(), args_str)
-            }            
         }
     }
+
+    // Displays a number with exactly the requested number of decimals, through the output-fixed procedure of the runtime
+    fn formatted(&mut self, value: &Operand, precision: Option<u32>) -> String {
+        match precision {
+            Some(decimals) => format!("(output-fixed {} {})", self.operand(value), decimals),
+            None => self.operand(value),
+        }
+    }
+
+    // Code for the value of an operand
+    fn operand(&mut self, operand: &Operand) -> String {
+        self.inliner.take(operand).unwrap_or_else(|| self.operand_name(operand))
+    }
+
+    // Name defined for an operand
+    fn operand_name(&self, operand: &Operand) -> String {
+        names::SCHEME.operand(operand)
+    }
+}
+
+fn column_to_scheme(column: &Column) -> String {
+    match column {
+        Column::Index(n) => n.to_string(),
+        Column::Name(name) => string_literal(name),
+    }
+}
+
+// String literal escaping quotes, backslashes and control characters; other characters are written as UTF-8,
+// which Racket, Guile and Chez read by default
+fn string_literal(text: &str) -> String {
+    backend::quoted(text, '"', |code| format!("\\x{:x};", code))
+}
+
+// Convert row filters to their Scheme predicate ('!=' has no direct equivalent so it is negated)
+fn filter_to_scheme(expr: &Expression) -> String {
+    match expr {
+        Expression::Comparison(left, comparator, right) => {
+            let operator = match comparator {
+                Comparator::Less => "<",
+                Comparator::LessEqual => "<=",
+                Comparator::Greater => ">",
+                Comparator::GreaterEqual => ">=",
+                Comparator::Equal | Comparator::NotEqual => "=",
+            };
+            let comparison = format!("({} {} {})", operator, filter_to_scheme(left), filter_to_scheme(right));
+            if *comparator == Comparator::NotEqual {
+                format!("(not {})", comparison)
+            } else {
+                comparison
+            }
+        },
+        Expression::FunctionCall(_, args) => {
+            let args_str = args.iter().map(filter_to_scheme).collect::<Vec<String>>();
+            format!("(col {})", args_str.join(" "))
+        },
+        Expression::BinaryOp(left, operator, right) => {
+            format!("({} {} {})", operator.symbol(), filter_to_scheme(left), filter_to_scheme(right))
+        },
+        Expression::Literal(LiteralValue::Num(n)) => n.to_string(),
+        _ => unreachable!("'where' filters only contain col(NUM), numbers and arithmetic"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer, parser, semantic, ir, optimize};
+    use crate::types::TypeEnv;
+
+    // Scheme code of the process section of a program reading x and displaying v
+    fn process_to_scheme(declarations: &str, process: &str) -> String {
+        let source = format!("data: x : vector, v : vector, {} input: x = read(\"a.csv\", true, 0) process: {} output: v end.", declarations, process);
+        let nodes = parser::Parser::new(lexer::lexical_analysis(&source).unwrap()).parse().unwrap();
+        let types = TypeEnv::from_symbols(&semantic::analyze(&nodes).0);
+        let mut program = ir::lower(&nodes, &types);
+        optimize::optimize(&mut program).unwrap();
+        convert_to_scheme(&program)
+    }
+
+    #[test]
+    fn slices_call_the_runtime() {
+        assert!(process_to_scheme("n : number", "n = 2, v = x[1:10]").contains("(define v (slice x 1 10))"));
+        let code = process_to_scheme("n : number", "n = mean(x), v = x[0:n]");
+        assert!(code.contains("(define v (slice x 0 n))"), "{}", code);
+    }
+
+    #[test]
+    fn reserved_names_are_prefixed() {
+        let code = process_to_scheme("list : vector, exact : number", "list = x * 2, exact = mean(list), v = list - exact");
+        assert!(code.contains("(define da-list (map (lambda (e_) (* e_ 2)) x))"), "{}", code);
+        assert!(code.contains("(define da-exact (mean da-list))"), "{}", code);
+        assert!(code.contains("(define v (map (lambda (e_) (- e_ da-exact)) da-list))"), "{}", code);
+    }
 }
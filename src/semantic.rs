@@ -0,0 +1,183 @@
+/*
+    This module runs semantic analysis on the parse tree: it builds the symbol table from the data section
+    and checks that every variable used by the program is declared once and assigned
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::lexer::Span;
+use crate::parser::{TreeNode, Declaration, ExternFunction};
+use crate::builtins;
+use crate::types::Type;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// A problem found in the program and the position where it was found
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Span>, // missing when the problem is not tied to a variable
+}
+
+impl Diagnostic {
+    pub fn error(message: String, span: Option<Span>) -> Self {
+        Diagnostic { severity: Severity::Error, message, span }
+    }
+
+    pub fn warning(message: String, span: Option<Span>) -> Self {
+        Diagnostic { severity: Severity::Warning, message, span }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "{} at {}", self.message, span),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+// What is known about a declared variable
+#[derive(Debug)]
+pub struct Symbol {
+    pub ty: Type,
+    pub span: Span, // position of the declaration
+}
+
+// Every variable declared in the data section and every function of the extern section
+pub struct SymbolTable {
+    symbols: HashMap<String, Symbol>,
+    externs: Vec<ExternFunction>,
+}
+
+impl SymbolTable {
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Symbol)> {
+        self.symbols.iter()
+    }
+
+    pub fn externs(&self) -> impl Iterator<Item = &ExternFunction> {
+        self.externs.iter()
+    }
+}
+
+// Builds the symbol table and reports duplicate declarations, undeclared variables (errors)
+// and declared variables that are never assigned (warnings), in the order they appear in the program
+pub fn analyze(nodes: &[TreeNode]) -> (SymbolTable, Vec<Diagnostic>) {
+    let mut symbols: HashMap<String, Symbol> = HashMap::new();
+    let mut declarations = Vec::new();
+    let mut diagnostics = Vec::new();
+
+    // extern functions may not redefine a builtin or another extern function
+    let mut externs: Vec<ExternFunction> = Vec::new();
+    for node in nodes {
+        if let TreeNode::Extern(functions) = node {
+            for function in functions {
+                if builtins::lookup(&function.name).is_some() {
+                    diagnostics.push(Diagnostic::error(
+                        format!("Extern function '{}' has the name of a builtin function", function.name), Some(function.span)));
+                } else if let Some(previous) = externs.iter().find(|previous| previous.name == function.name) {
+                    diagnostics.push(Diagnostic::error(
+                        format!("Extern function '{}' is declared twice (first declaration at {})", function.name, previous.span), Some(function.span)));
+                } else {
+                    externs.push(function.clone());
+                }
+            }
+        }
+    }
+
+    // the data section fills the symbol table
+    for node in nodes {
+        if let TreeNode::Data(decls) = node {
+            for declaration in decls {
+                let (name, ty, span) = match declaration {
+                    Declaration::Vector(name, span) => (name, Type::Vector, *span),
+                    Declaration::Number(name, span) => (name, Type::Number, *span),
+                    Declaration::Record(name, span) => (name, Type::Record, *span),
+                };
+                if let Some(previous) = symbols.get(name) {
+                    diagnostics.push(Diagnostic::error(
+                        format!("Variable '{}' is declared twice (first declaration at {})", name, previous.span), Some(span)));
+                } else {
+                    symbols.insert(name.clone(), Symbol { ty, span });
+                    declarations.push(name.as_str());
+                }
+            }
+        }
+    }
+
+    // every other section may only assign and read declared variables
+    let mut assigned = HashSet::new();
+    let check_declared = |name: &str, span: Span, diagnostics: &mut Vec<Diagnostic>| {
+        if !symbols.contains_key(name) {
+            diagnostics.push(Diagnostic::error(format!("Undeclared variable '{}'", name), Some(span)));
+        }
+    };
+    for node in nodes {
+        match node {
+            TreeNode::Input(assignments) | TreeNode::Process(assignments) => {
+                for assignment in assignments {
+                    for (name, span) in assignment.expression().identifiers() {
+                        check_declared(name, span, &mut diagnostics);
+                    }
+                    for (name, span) in assignment.targets() {
+                        check_declared(name, span, &mut diagnostics);
+                        assigned.insert(name);
+                    }
+                }
+            },
+            TreeNode::Output(_, exprs) => {
+                for expr in exprs {
+                    for (name, span) in expr.identifiers() {
+                        check_declared(name, span, &mut diagnostics);
+                    }
+                }
+            },
+            TreeNode::Extern(_) | TreeNode::Data(_) | TreeNode::End => {},
+        }
+    }
+
+    for name in declarations {
+        if !assigned.contains(name) {
+            diagnostics.push(Diagnostic::warning(
+                format!("Variable '{}' is declared but never assigned", name), Some(symbols[name].span)));
+        }
+    }
+
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.map(|span| (span.line, span.column)));
+    (SymbolTable { symbols, externs }, diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer, parser};
+
+    fn diagnostics(source: &str) -> Vec<Diagnostic> {
+        let nodes = parser::Parser::new(lexer::lexical_analysis(source).unwrap()).parse().unwrap();
+        analyze(&nodes).1
+    }
+
+    #[test]
+    fn never_assigned_variable_is_a_warning_at_its_declaration() {
+        let found = diagnostics("data:\n x : vector,\n n : number\ninput:\n x = read(\"a.csv\", true, 1)\nend.");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].severity, Severity::Warning);
+        assert_eq!(found[0].message, "Variable 'n' is declared but never assigned");
+        assert_eq!(found[0].span, Some(Span { line: 3, column: 2 }));
+    }
+
+    #[test]
+    fn undeclared_and_duplicate_variables_are_errors() {
+        let found = diagnostics("data: x : vector, x : number input: x = read(\"a.csv\", true, 1) process: y = mean(x) end.");
+        let messages: Vec<&str> = found.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+        assert_eq!(messages, ["Variable 'x' is declared twice (first declaration at line 1, column 7)", "Undeclared variable 'y'"]);
+        assert!(found.iter().all(|diagnostic| diagnostic.severity == Severity::Error));
+    }
+}
@@ -0,0 +1,354 @@
+/*
+    This module holds the types of DA values and the rules used to infer the type of an expression
+*/
+
+use std::collections::HashMap;
+
+use crate::parser::{TreeNode, Assignment, Expression, LiteralValue, Segment};
+use crate::semantic::{SymbolTable, Diagnostic};
+use crate::builtins::{self, Function};
+use crate::parser::ExternFunction;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Type {
+    Number,
+    Vector,
+    Record, // summary statistics returned by describe()
+    Tuple(usize), // several numbers returned at once, only usable in a multiple assignment
+    Table, // CSV file loaded once for several reads, only used by the generated code
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Type::Number => write!(f, "number"),
+            Type::Vector => write!(f, "vector"),
+            Type::Record => write!(f, "record"),
+            Type::Tuple(count) => write!(f, "tuple of {} numbers", count),
+            Type::Table => write!(f, "table"),
+        }
+    }
+}
+
+// Fields of the record returned by describe(), all of them numbers
+pub const RECORD_FIELDS: [&str; 5] = ["count", "mean", "stddev", "min", "max"];
+
+// Declared type of every variable of the program, taken from the data section
+pub struct TypeEnv {
+    types: HashMap<String, Type>,
+    externs: HashMap<String, ExternFunction>, // signatures of the extern section
+}
+
+impl TypeEnv {
+
+    // Builds the environment from the declarations collected in the symbol table
+    pub fn from_symbols(symbols: &SymbolTable) -> Self {
+        let types = symbols.iter().map(|(name, symbol)| (name.clone(), symbol.ty)).collect();
+        let externs = symbols.externs().map(|function| (function.name.clone(), function.clone())).collect();
+        TypeEnv { types, externs }
+    }
+
+    // Checks every operation of the program against the declared types:
+    //   - read() returns a vector, so it can only be assigned to a vector
+    //   - a 'where' filter compares two operands built from col(NUM), numbers and arithmetic
+    //   - the value of a process operation must have the declared type of the assigned variable
+    //   - functions must be called with arguments of the types of their signature
+    // Errors are reported at the position of the assigned variable (or of the first variable of an output operation)
+    pub fn check(&self, nodes: &[TreeNode]) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for node in nodes {
+            match node {
+                TreeNode::Input(assignments) => {
+                    for (name, span) in assignments.iter().flat_map(|assignment| assignment.targets()) {
+                        if let Some(declared) = self.types.get(name).filter(|&&declared| declared != Type::Vector) {
+                            diagnostics.push(Diagnostic::error(
+                                format!("read() returns a vector but '{}' is declared as a {}", name, declared), Some(span)));
+                        }
+                    }
+                    for assignment in assignments {
+                        if let Assignment::Assign(_, span, Expression::FunctionCall(_, args)) = assignment {
+                            if let Err(e) = args.get(3).map_or(Ok(()), check_filter) {
+                                diagnostics.push(Diagnostic::error(e, Some(*span)));
+                            }
+                        }
+                    }
+                },
+                TreeNode::Process(assignments) => {
+                    for assignment in assignments {
+                        if let Err(e) = self.check_assignment(assignment) {
+                            let span = assignment.targets().first().map(|(_, span)| *span);
+                            diagnostics.push(Diagnostic::error(e, span));
+                        }
+                    }
+                },
+                TreeNode::Output(_, exprs) => {
+                    for expr in exprs {
+                        if let Err(e) = self.check_output(expr) {
+                            let span = expr.identifiers().first().map(|(_, span)| *span);
+                            diagnostics.push(Diagnostic::error(e, span));
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+        diagnostics
+    }
+
+    // Checks that a process operation assigns values of the declared types
+    fn check_assignment(&self, assignment: &Assignment) -> Result<(), String> {
+        match assignment {
+            Assignment::Assign(name, _, expr) => {
+                let actual = self.type_of(expr)?;
+                if let Type::Tuple(count) = actual {
+                    return Err(format!("Expression assigned to '{}' returns {} values, assign them with 'a, b = ...'", name, count));
+                }
+                match self.types.get(name) {
+                    Some(&declared) if declared != actual => {
+                        Err(format!("Cannot assign a {} to '{}' declared as a {}", actual, name, declared))
+                    },
+                    _ => Ok(()),
+                }
+            },
+            Assignment::Destructure(names, expr) => match self.type_of(expr)? {
+                Type::Tuple(count) if count == names.len() => {
+                    // every value of a tuple is a number
+                    for (name, _) in names {
+                        match self.types.get(name) {
+                            Some(&declared) if declared != Type::Number => {
+                                return Err(format!("Cannot assign a number to '{}' declared as a {}", name, declared));
+                            },
+                            _ => {}
+                        }
+                    }
+                    Ok(())
+                },
+                Type::Tuple(count) => Err(format!("Expression returns {} values but {} variables are assigned", count, names.len())),
+                _ => Err("Only functions returning several values can be assigned to several variables".to_string()),
+            },
+        }
+    }
+
+    // Checks an output operation - only numbers can be displayed with a number of decimals
+    fn check_output(&self, expr: &Expression) -> Result<(), String> {
+        match expr {
+            // labels are displayed as they are and have no numeric type
+            Expression::Literal(LiteralValue::Str(_)) => Ok(()),
+            Expression::Interpolation(segments) => {
+                for segment in segments {
+                    match segment {
+                        Segment::Value(value, Some(_)) => self.expect(value, Type::Number, "Only numbers can be formatted with a number of decimals")?,
+                        Segment::Value(value, None) => { self.type_of(value)?; },
+                        Segment::Text(_) => {}
+                    }
+                }
+                Ok(())
+            },
+            Expression::Formatted(value, _) => self.expect(value, Type::Number, "Only numbers can be formatted with a number of decimals"),
+            // the variables saved by write() may be numbers or vectors
+            Expression::FunctionCall(name, args) if name == "write" => {
+                for value in &args[1..] {
+                    self.type_of(value)?;
+                }
+                Ok(())
+            },
+            _ => self.type_of(expr).map(|_| ()),
+        }
+    }
+
+    // Infers the type of an expression:
+    //   - arithmetic between two numbers is a number, as soon as one side is a vector it is applied element-wise
+    //   - regression() returns a tuple of two numbers, describe() a record whose fields are numbers
+    //   - indexing a vector gives a number, slicing a vector gives a vector
+    //   - vector literals may only contain numbers
+    pub fn type_of(&self, expr: &Expression) -> Result<Type, String> {
+        match expr {
+            Expression::Literal(LiteralValue::Num(_)) => Ok(Type::Number),
+            Expression::Literal(lit) => Err(format!("{:?} is neither a number nor a vector", lit)),
+            Expression::Identifier(name, _) => self.types.get(name).copied()
+                .ok_or_else(|| format!("Undeclared variable '{}'", name)),
+            Expression::FunctionCall(name, _) if name == "read" => Ok(Type::Vector),
+            // builtin and extern functions are checked against their signature
+            Expression::FunctionCall(name, args) => {
+                let function = self.function(name).ok_or_else(|| format!("Unknown function '{}'", name))?;
+                let parameters = function.parameters();
+                if args.len() != parameters.len() {
+                    return Err(format!("{}() expects {} argument(s) but {} were given", name, parameters.len(), args.len()));
+                }
+                for (position, (arg, &parameter)) in args.iter().zip(parameters).enumerate() {
+                    let actual = self.type_of(arg)?;
+                    if actual != parameter {
+                        return Err(format!("{}() expects a {} as argument {}, found a {}", name, parameter, position + 1, actual));
+                    }
+                }
+                Ok(function.result())
+            },
+            Expression::Field(record, field) => {
+                self.expect(record, Type::Record, "Only records have fields")?;
+                if RECORD_FIELDS.contains(&field.as_str()) {
+                    Ok(Type::Number)
+                } else {
+                    Err(format!("Unknown field '{}', records have the fields {}", field, RECORD_FIELDS.join(", ")))
+                }
+            },
+            Expression::Comparison(..) => Err("Comparisons are only allowed in a 'where' clause".to_string()),
+            Expression::Interpolation(_) | Expression::Formatted(..) => Err("Formatted values are only allowed in the output section".to_string()),
+            Expression::VectorLiteral(elements) => {
+                for element in elements {
+                    if self.type_of(element)? != Type::Number {
+                        return Err("Elements of a vector literal must be numbers".to_string());
+                    }
+                }
+                Ok(Type::Vector)
+            },
+            Expression::Index(vector, index) => {
+                self.expect(vector, Type::Vector, "Only vectors can be indexed")?;
+                self.expect(index, Type::Number, "Vector index must be a number")?;
+                Ok(Type::Number)
+            },
+            Expression::Slice(vector, start, end) => {
+                self.expect(vector, Type::Vector, "Only vectors can be sliced")?;
+                self.expect(start, Type::Number, "Slice bounds must be numbers")?;
+                self.expect(end, Type::Number, "Slice bounds must be numbers")?;
+                Ok(Type::Vector)
+            },
+            Expression::BinaryOp(left, _, right) => {
+                match (self.type_of(left)?, self.type_of(right)?) {
+                    (Type::Number, Type::Number) => Ok(Type::Number),
+                    (Type::Number | Type::Vector, Type::Number | Type::Vector) => Ok(Type::Vector),
+                    _ => Err("Arithmetic is only defined on numbers and vectors".to_string()),
+                }
+            },
+        }
+    }
+
+    // Finds a builtin function, or else a function of the extern section
+    pub fn function(&self, name: &str) -> Option<Function<'_>> {
+        builtins::lookup(name).map(Function::Builtin)
+            .or_else(|| self.externs.get(name).map(Function::Extern))
+    }
+
+    // Declared type of a variable
+    pub fn variable_type(&self, name: &str) -> Option<Type> {
+        self.types.get(name).copied()
+    }
+
+    // Returns an error with the given message if the expression does not have the expected type
+    fn expect(&self, expr: &Expression, expected: Type, message: &str) -> Result<(), String> {
+        let actual = self.type_of(expr)?;
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(format!("{}, found a {}", message, actual))
+        }
+    }
+}
+
+// A 'where' filter is a single comparison, e.g. col(3) * 2 > 100 - the generated code evaluates it on the rows
+// of the file, so it cannot use program variables
+fn check_filter(filter: &Expression) -> Result<(), String> {
+    match filter {
+        Expression::Comparison(left, _, right) => {
+            check_filter_operand(left)?;
+            check_filter_operand(right)
+        },
+        _ => Err("A 'where' clause must be a single comparison".to_string()),
+    }
+}
+
+fn check_filter_operand(expr: &Expression) -> Result<(), String> {
+    match expr {
+        Expression::Literal(LiteralValue::Num(_)) => Ok(()),
+        Expression::FunctionCall(name, args) if name == "col" => match args.as_slice() {
+            [Expression::Literal(LiteralValue::Num(_))] => Ok(()),
+            _ => Err("col() in a 'where' clause takes the number of a column, e.g. col(3)".to_string()),
+        },
+        Expression::BinaryOp(left, _, right) => {
+            check_filter_operand(left)?;
+            check_filter_operand(right)
+        },
+        Expression::Identifier(name, _) => Err(format!("The 'where' clause cannot use the variable '{}', only col(NUM) and numbers", name)),
+        Expression::Comparison(..) => Err("A 'where' clause must be a single comparison".to_string()),
+        _ => Err("Only col(NUM), numbers and arithmetic can be compared in a 'where' clause".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer, parser, semantic};
+
+    // Messages of the type errors of a program that passes the semantic analysis
+    fn type_errors(source: &str) -> Vec<String> {
+        let nodes = parser::Parser::new(lexer::lexical_analysis(source).unwrap()).parse().unwrap();
+        let (symbols, diagnostics) = semantic::analyze(&nodes);
+        assert!(diagnostics.iter().all(|diagnostic| diagnostic.severity == semantic::Severity::Warning), "{:?}", diagnostics);
+        TypeEnv::from_symbols(&symbols).check(&nodes).into_iter().map(|diagnostic| diagnostic.message).collect()
+    }
+
+    fn filtered_read(filter: &str) -> Vec<String> {
+        type_errors(&format!("data: x : vector, n : number input: x = read(\"a.csv\", true, 1) where {} end.", filter))
+    }
+
+    fn processed(declarations: &str, process: &str) -> Vec<String> {
+        type_errors(&format!("data: x : vector, {} input: x = read(\"a.csv\", true, 1) process: {} end.", declarations, process))
+    }
+
+    #[test]
+    fn arithmetic_with_a_vector_is_a_vector() {
+        assert!(processed("n : number, v : vector", "n = mean(x) * 2, v = x - n").is_empty());
+        assert_eq!(processed("n : number", "n = x * 2"), ["Cannot assign a vector to 'n' declared as a number"]);
+        assert_eq!(processed("v : vector", "v = mean(x) + 1"), ["Cannot assign a number to 'v' declared as a vector"]);
+    }
+
+    #[test]
+    fn read_can_only_be_assigned_to_a_vector() {
+        assert_eq!(type_errors("data: n : number input: n = read(\"a.csv\", true, 1) end."),
+            ["read() returns a vector but 'n' is declared as a number"]);
+    }
+
+    #[test]
+    fn function_arguments_are_checked_against_the_signature() {
+        assert_eq!(processed("n : number", "n = mean(mean(x))"), ["mean() expects a vector as argument 1, found a number"]);
+        assert_eq!(processed("n : number", "n = correlation(x)"), ["correlation() expects 2 argument(s) but 1 were given"]);
+    }
+
+    #[test]
+    fn indexes_slices_and_fields() {
+        assert!(processed("n : number, v : vector, s : record", "n = x[0], v = x[1:3], s = describe(x), n = s.mean").is_empty());
+        assert_eq!(processed("n : number", "n = x[x]"), ["Vector index must be a number, found a vector"]);
+        assert_eq!(processed("n : number, s : record", "s = describe(x), n = s.median"),
+            ["Unknown field 'median', records have the fields count, mean, stddev, min, max"]);
+    }
+
+    #[test]
+    fn tuples_must_be_assigned_to_as_many_numbers() {
+        assert!(processed("a : number, b : number", "a, b = regression(x, x)").is_empty());
+        assert_eq!(processed("a : number", "a = regression(x, x)"), ["Expression assigned to 'a' returns 2 values, assign them with 'a, b = ...'"]);
+        assert_eq!(processed("a : number, b : number, c : number", "a, b, c = regression(x, x)"), ["Expression returns 2 values but 3 variables are assigned"]);
+        assert_eq!(processed("a : number, b : vector", "a, b = regression(x, x)"), ["Cannot assign a number to 'b' declared as a vector"]);
+    }
+
+    #[test]
+    fn only_numbers_are_formatted() {
+        assert_eq!(type_errors("data: x : vector input: x = read(\"a.csv\", true, 1) output: x:.2 end."),
+            ["Only numbers can be formatted with a number of decimals, found a vector"]);
+    }
+
+    #[test]
+    fn where_filter_accepts_columns_numbers_and_arithmetic() {
+        assert!(filtered_read("col(1) * 2 >= 3").is_empty());
+        assert!(filtered_read("(col(1) + col(2)) / 2 < col(3) - 1").is_empty());
+    }
+
+    #[test]
+    fn where_filter_rejects_variables() {
+        assert_eq!(filtered_read("col(1) > n"), ["The 'where' clause cannot use the variable 'n', only col(NUM) and numbers"]);
+    }
+
+    #[test]
+    fn where_filter_rejects_other_functions_and_named_columns() {
+        assert_eq!(filtered_read("mean(col(1)) > 3"), ["Only col(NUM), numbers and arithmetic can be compared in a 'where' clause"]);
+        assert_eq!(filtered_read("col(\"price\") > 3"), ["col() in a 'where' clause takes the number of a column, e.g. col(3)"]);
+    }
+}